@@ -0,0 +1,236 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Tagged representation of a JavaScript value produced by `XtalInspect` in
+/// the runtime. Values that JSON can't carry (`undefined`, `NaN`, `BigInt`,
+/// functions, collections, ...) are described instead of serialized.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Inspected {
+    Undefined,
+    Null,
+    Boolean { value: bool },
+    // Kept as a string so `NaN`, `Infinity` and `-0` survive the round-trip.
+    Number { value: String },
+    Bigint { value: String },
+    String {
+        value: String,
+        // Characters cut off by `max_string_length`.
+        #[serde(default)]
+        more: usize,
+    },
+    Symbol { description: String },
+    Function { name: String, class: bool },
+    // `more` counts the items cut off by `max_array_length`.
+    Array {
+        items: Vec<Inspected>,
+        #[serde(default)]
+        more: usize,
+    },
+    Map {
+        entries: Vec<(Inspected, Inspected)>,
+        #[serde(default)]
+        more: usize,
+    },
+    Set {
+        values: Vec<Inspected>,
+        #[serde(default)]
+        more: usize,
+    },
+    Date { value: String },
+    Regexp { value: String },
+    Typedarray {
+        constructor: String,
+        items: Vec<String>,
+        #[serde(default)]
+        more: usize,
+    },
+    Error { name: String, message: String },
+    Object { constructor: Option<String>, entries: Vec<(String, Inspected)> },
+    // A value that contains a reference back to itself, `id` is what the
+    // `Circular` markers inside of it point at.
+    Ref { id: u32, value: Box<Inspected> },
+    Circular { id: u32 },
+    // Nested deeper than `depth`, only the constructor name is kept.
+    Truncated { label: String },
+}
+
+/// Limits the runtime inspector applies before a value leaves the isolate,
+/// the defaults match Node's `util.inspect`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InspectOptions {
+    pub depth: usize,
+    pub max_array_length: usize,
+    pub max_string_length: usize,
+}
+
+impl Default for InspectOptions {
+    fn default() -> Self {
+        Self {
+            depth: 2,
+            max_array_length: 100,
+            max_string_length: 10_000,
+        }
+    }
+}
+
+impl InspectOptions {
+    /// Script that installs the options for `XtalInspect`.
+    pub fn to_script(&self) -> String {
+        format!(
+            "globalThis.XtalInspectOptions = {{ depth: {}, maxArrayLength: {}, maxStringLength: {} }};",
+            self.depth, self.max_array_length, self.max_string_length
+        )
+    }
+}
+
+impl Inspected {
+    /// Renders the value the way `console.log` does: strings at the top level
+    /// are printed as is, everything else like Node's `util.inspect`.
+    pub fn render(&self) -> String {
+        match self {
+            Inspected::String { value, more } => format!("{}{}", value, more_characters(*more)),
+            other => other.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Inspected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Inspected::Undefined => write!(f, "undefined"),
+            Inspected::Null => write!(f, "null"),
+            Inspected::Boolean { value } => write!(f, "{}", value),
+            Inspected::Number { value } => write!(f, "{}", value),
+            Inspected::Bigint { value } => write!(f, "{}n", value),
+            Inspected::String { value, more } => write!(f, "{}{}", quote(value), more_characters(*more)),
+            Inspected::Symbol { description } => write!(f, "Symbol({})", description),
+            Inspected::Function { name, class: true } if name.is_empty() => {
+                write!(f, "[class (anonymous)]")
+            }
+            Inspected::Function { name, class: true } => write!(f, "[class {}]", name),
+            Inspected::Function { name, .. } if name.is_empty() => {
+                write!(f, "[Function (anonymous)]")
+            }
+            Inspected::Function { name, .. } => write!(f, "[Function: {}]", name),
+            Inspected::Array { items, more } => write_list(
+                f,
+                "",
+                "[",
+                items.iter().map(|item| item.to_string()).chain(more_items(*more)),
+                "]",
+            ),
+            Inspected::Map { entries, more } => write_list(
+                f,
+                &format!("Map({}) ", entries.len() + more),
+                "{",
+                entries
+                    .iter()
+                    .map(|(key, value)| format!("{} => {}", key, value))
+                    .chain(more_items(*more)),
+                "}",
+            ),
+            Inspected::Set { values, more } => write_list(
+                f,
+                &format!("Set({}) ", values.len() + more),
+                "{",
+                values.iter().map(|value| value.to_string()).chain(more_items(*more)),
+                "}",
+            ),
+            Inspected::Date { value } => write!(f, "{}", value),
+            Inspected::Regexp { value } => write!(f, "{}", value),
+            Inspected::Typedarray { constructor, items, more } => write_list(
+                f,
+                &format!("{}({}) ", constructor, items.len() + more),
+                "[",
+                items.iter().cloned().chain(more_items(*more)),
+                "]",
+            ),
+            Inspected::Error { name, message } if message.is_empty() => write!(f, "[{}]", name),
+            Inspected::Error { name, message } => write!(f, "[{}: {}]", name, message),
+            Inspected::Object { constructor, entries } => {
+                let prefix = match constructor.as_deref() {
+                    None => "[Object: null prototype] ".to_string(),
+                    Some("Object") => String::new(),
+                    Some(name) => format!("{} ", name),
+                };
+                write_list(
+                    f,
+                    &prefix,
+                    "{",
+                    entries.iter().map(|(key, value)| format!("{}: {}", property_key(key), value)),
+                    "}",
+                )
+            }
+            Inspected::Ref { id, value } => write!(f, "<ref *{}> {}", id, value),
+            Inspected::Circular { id } => write!(f, "[Circular *{}]", id),
+            Inspected::Truncated { label } => write!(f, "[{}]", label),
+        }
+    }
+}
+
+fn more_items(more: usize) -> Option<String> {
+    match more {
+        0 => None,
+        1 => Some("... 1 more item".to_string()),
+        more => Some(format!("... {} more items", more)),
+    }
+}
+
+fn more_characters(more: usize) -> String {
+    match more {
+        0 => String::new(),
+        1 => "... 1 more character".to_string(),
+        more => format!("... {} more characters", more),
+    }
+}
+
+/// Writes `prefix{ a, b }`, or `prefix{}` when there are no items.
+fn write_list(
+    f: &mut fmt::Formatter<'_>,
+    prefix: &str,
+    open: &str,
+    items: impl Iterator<Item = String>,
+    close: &str,
+) -> fmt::Result {
+    let items = items.collect::<Vec<_>>();
+    if items.is_empty() {
+        write!(f, "{}{}{}", prefix, open, close)
+    } else {
+        write!(f, "{}{} {} {}", prefix, open, items.join(", "), close)
+    }
+}
+
+fn quote(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('\'');
+    for c in value.chars() {
+        match c {
+            '\'' => quoted.push_str("\\'"),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('\'');
+    quoted
+}
+
+/// Object keys are printed bare when they are valid identifiers.
+fn property_key(key: &str) -> String {
+    let mut chars = key.chars();
+    let is_identifier = chars
+        .next()
+        .is_some_and(|c| c.is_alphabetic() || c == '_' || c == '$')
+        && chars.all(|c| c.is_alphanumeric() || c == '_' || c == '$');
+
+    if is_identifier {
+        key.to_string()
+    } else {
+        quote(key)
+    }
+}
@@ -1,27 +1,163 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashSet;
+
 use oxc_allocator::{Allocator, Box, CloneIn};
 use oxc_ast::{ast::*, visit::walk_mut::*, AstBuilder, VisitMut, NONE};
-use oxc_span::SPAN;
+use oxc_semantic::SemanticBuilder;
+use oxc_span::{Atom, GetSpan, Span, SPAN};
+
+use super::line_index::LineIndex;
+use super::utils::{CoverageKind, ResultKind};
 
-use super::utils::get_line_number;
+const TIMER_FUNCTIONS: [&str; 3] = ["setTimeout", "setInterval", "queueMicrotask"];
+// Globals the snippet calls directly and the helpers their calls are
+// rewritten to, so results and failures know where they came from.
+const HELPER_FUNCTIONS: [(&str, &str); 3] = [
+    ("test", "XtalTest"),
+    ("expect", "XtalExpect"),
+    ("bench", "XtalBench"),
+];
 
 pub struct AstReplacer<'a> {
-    source_text: String,
+    line_index: &'a LineIndex,
+    options: AstReplacerOptions,
     ast_builder: AstBuilder<'a>,
-    allocator: &'a Allocator
+    allocator: &'a Allocator,
+    // Every Xtal call is registered with the span it reports on, the kind of
+    // a result is looked up by that span.
+    sites: RefCell<std::vec::Vec<Site>>,
+    // Span and kind the next Xtal call is registered with.
+    pending_site: Cell<(Span, ResultKind)>,
+    // Spans of the references to `test`, `expect` and `bench` that don't
+    // resolve to a binding of the snippet, only these are rewritten to the
+    // helpers. Spans rather than reference ids, which aren't kept when an
+    // expression is cloned.
+    helper_references: HashSet<Span>,
+    // Statements and branches that report through `XtalCov`, the index is
+    // the id they report with.
+    coverage: RefCell<std::vec::Vec<CoveragePoint>>,
+    // Set while the body of a labeled statement is visited, a labeled loop
+    // is entered through its label.
+    in_label: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AstReplacerOptions {
+    // Session runs are evaluated as scripts in a long-lived runtime. Top-level
+    // `let`/`const` become `var` there, so bindings survive between runs and
+    // can be declared again by the next one.
+    pub session: bool,
+    // Report how long every wrapped expression took, see `XtalTimed`.
+    pub timing: bool,
+    // Count which statements and branches ran, see `XtalCov`.
+    pub coverage: bool,
+}
+
+/// Source location of an instrumented expression, its span is passed to `Xtal`.
+#[derive(Debug, Clone, Copy)]
+pub struct Site {
+    pub line: usize,
+    pub span: Span,
+    pub kind: ResultKind,
+}
+
+/// Statement or branch counted by an `XtalCov(id)` call.
+#[derive(Debug, Clone, Copy)]
+pub struct CoveragePoint {
+    pub span: Span,
+    pub kind: CoverageKind,
 }
 
 impl<'a> AstReplacer<'a> {
-    pub fn new(allocator: &'a Allocator, source_text: String) -> Self {
+    pub fn new(
+        allocator: &'a Allocator,
+        line_index: &'a LineIndex,
+        options: AstReplacerOptions,
+    ) -> Self {
         let ast_builder = AstBuilder::new(allocator);
         Self {
             ast_builder,
-            source_text,
-            allocator
+            line_index,
+            options,
+            allocator,
+            sites: RefCell::new(std::vec::Vec::new()),
+            pending_site: Cell::new((SPAN, ResultKind::Expression)),
+            helper_references: HashSet::new(),
+            coverage: RefCell::new(std::vec::Vec::new()),
+            in_label: false,
         }
     }
 
     pub fn build(&mut self, program: &mut Program<'a>) {
+        // A snippet's own `test`, `expect` or `bench`, at any scope, is left
+        // alone.
+        let semantic = SemanticBuilder::new().build(program).semantic;
+        let unresolved = semantic.scopes().root_unresolved_references();
+        self.helper_references = HELPER_FUNCTIONS
+            .iter()
+            .filter_map(|(name, _)| unresolved.get(*name))
+            .flatten()
+            .map(|reference_id| {
+                let node_id = semantic.symbols().get_reference(*reference_id).node_id();
+                semantic.nodes().get_node(node_id).kind().span()
+            })
+            .collect();
+
         self.visit_program(program);
+
+        if self.options.session {
+            for statement in program.body.iter_mut() {
+                match statement {
+                    Statement::VariableDeclaration(declaration) => {
+                        declaration.kind = VariableDeclarationKind::Var;
+                    }
+                    Statement::ClassDeclaration(class) if !class.declare && class.id.is_some() => {
+                        self.class_to_var(statement);
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    /// Rewrites `class Foo {}` to `var Foo = class Foo {}`. A class
+    /// declaration can't be declared again in the same global scope, the
+    /// next session run would fail on it.
+    fn class_to_var(&self, statement: &mut Statement<'a>) {
+        let Statement::ClassDeclaration(mut class) = self.ast_builder.move_statement(statement)
+        else {
+            return;
+        };
+        let Some(id) = class.id.clone() else {
+            return;
+        };
+
+        let span = class.span;
+        class.r#type = ClassType::ClassExpression;
+        let binding = self.ast_builder.binding_pattern(
+            self.ast_builder
+                .binding_pattern_kind_binding_identifier(id.span, id.name),
+            NONE,
+            false,
+        );
+        let declarator = self.ast_builder.variable_declarator(
+            span,
+            VariableDeclarationKind::Var,
+            binding,
+            Some(Expression::ClassExpression(class)),
+            false,
+        );
+        *statement = Statement::VariableDeclaration(self.ast_builder.alloc_variable_declaration(
+            span,
+            VariableDeclarationKind::Var,
+            self.ast_builder.vec1(declarator),
+            false,
+        ));
+    }
+
+    /// Sites and coverage points registered while building.
+    pub fn into_parts(self) -> (std::vec::Vec<Site>, std::vec::Vec<CoveragePoint>) {
+        (self.sites.into_inner(), self.coverage.into_inner())
     }
 
     fn handle_conditional_expression(
@@ -34,7 +170,7 @@ impl<'a> AstReplacer<'a> {
             let mut args = self.ast_builder.vec();
             args.push(Argument::from(
                 self.ast_builder.expression_conditional(
-                    SPAN,
+                    expr_stmt.span,
                     expr_stmt.test.clone_in(&self.allocator),
                     expr_stmt.consequent.clone_in(&self.allocator),
                     expr_stmt.alternate.clone_in(&self.allocator)
@@ -56,7 +192,7 @@ impl<'a> AstReplacer<'a> {
             let mut args = self.ast_builder.vec();
             args.push(Argument::from(
                 self.ast_builder.expression_template_literal(
-                    SPAN,
+                    expr_stmt.span,
                     expr_stmt.quasis.clone_in(&self.allocator),
                     expr_stmt.expressions.clone_in(&self.allocator),
                 ),
@@ -76,7 +212,7 @@ impl<'a> AstReplacer<'a> {
         let call_expr = self.create_debug_call(line, {
             let mut args = self.ast_builder.vec();
             args.push(Argument::from(self.ast_builder.expression_array(
-                SPAN,
+                expr_stmt.span,
                 expr_stmt.elements.clone_in(&self.allocator),
                 expr_stmt.trailing_comma.clone(),
             )));
@@ -95,7 +231,7 @@ impl<'a> AstReplacer<'a> {
         let call_expr = self.create_debug_call(line, {
             let mut args = self.ast_builder.vec();
             args.push(Argument::from(self.ast_builder.expression_parenthesized(
-                SPAN,
+                expr_stmt.span,
                 expr_stmt.expression.clone_in(&self.allocator),
             )));
             args
@@ -113,7 +249,7 @@ impl<'a> AstReplacer<'a> {
         let call_expr = self.create_debug_call(line, {
             let mut args = self.ast_builder.vec();
             args.push(Argument::from(self.ast_builder.expression_binary(
-                SPAN,
+                expr_stmt.span,
                 expr_stmt.left.clone_in(&self.allocator),
                 expr_stmt.operator.clone(),
                 expr_stmt.right.clone_in(&self.allocator),
@@ -133,7 +269,7 @@ impl<'a> AstReplacer<'a> {
         let call_expr = self.create_debug_call(line, {
             let mut args = self.ast_builder.vec();
             args.push(Argument::from(self.ast_builder.expression_logical(
-                SPAN,
+                expr_stmt.span,
                 expr_stmt.left.clone_in(&self.allocator),
                 expr_stmt.operator.clone(),
                 expr_stmt.right.clone_in(&self.allocator),
@@ -144,6 +280,107 @@ impl<'a> AstReplacer<'a> {
         it.expression = Expression::CallExpression(Box::new_in(call_expr, &self.allocator));
     }
 
+    fn handle_assignment_expression(
+        &mut self,
+        it: &mut ExpressionStatement<'a>,
+        expr_stmt: &AssignmentExpression<'a>,
+        line: usize,
+    ) {
+        let call_expr = self.create_debug_call(line, {
+            let mut args = self.ast_builder.vec();
+            args.push(Argument::from(self.ast_builder.expression_assignment(
+                expr_stmt.span,
+                expr_stmt.operator,
+                expr_stmt.left.clone_in(&self.allocator),
+                expr_stmt.right.clone_in(&self.allocator),
+            )));
+            args
+        });
+
+        it.expression = Expression::CallExpression(Box::new_in(call_expr, &self.allocator));
+    }
+
+    fn handle_update_expression(
+        &mut self,
+        it: &mut ExpressionStatement<'a>,
+        expr_stmt: &UpdateExpression<'a>,
+        line: usize,
+    ) {
+        // As a statement `i++` and `++i` have the same effect, the prefix form
+        // is emitted so the updated value is reported instead of the old one.
+        let call_expr = self.create_debug_call(line, {
+            let mut args = self.ast_builder.vec();
+            args.push(Argument::from(self.ast_builder.expression_update(
+                expr_stmt.span,
+                expr_stmt.operator,
+                true,
+                expr_stmt.argument.clone_in(&self.allocator),
+            )));
+            args
+        });
+
+        it.expression = Expression::CallExpression(Box::new_in(call_expr, &self.allocator));
+    }
+
+    fn handle_unary_expression(
+        &mut self,
+        it: &mut ExpressionStatement<'a>,
+        expr_stmt: &UnaryExpression<'a>,
+        line: usize,
+    ) {
+        let call_expr = self.create_debug_call(line, {
+            let mut args = self.ast_builder.vec();
+            args.push(Argument::from(self.ast_builder.expression_unary(
+                expr_stmt.span,
+                expr_stmt.operator,
+                expr_stmt.argument.clone_in(&self.allocator),
+            )));
+            args
+        });
+
+        it.expression = Expression::CallExpression(Box::new_in(call_expr, &self.allocator));
+    }
+
+    /// `await value` is reported with the awaited result, the statement
+    /// still waits for it before the next one runs.
+    fn handle_await_expression(
+        &mut self,
+        it: &mut ExpressionStatement<'a>,
+        expr_stmt: &AwaitExpression<'a>,
+        line: usize,
+    ) {
+        let call_expr = self.create_debug_call(line, {
+            let mut args = self.ast_builder.vec();
+            args.push(Argument::from(self.ast_builder.expression_await(
+                expr_stmt.span,
+                expr_stmt.argument.clone_in(&self.allocator),
+            )));
+            args
+        });
+
+        it.expression = Expression::CallExpression(Box::new_in(call_expr, &self.allocator));
+    }
+
+    fn handle_sequence_expression(
+        &mut self,
+        it: &mut ExpressionStatement<'a>,
+        expr_stmt: &SequenceExpression<'a>,
+        line: usize,
+    ) {
+        // The whole sequence is a single argument, every expression still runs
+        // once and in order, and its last value is reported.
+        let call_expr = self.create_debug_call(line, {
+            let mut args = self.ast_builder.vec();
+            args.push(Argument::from(self.ast_builder.expression_sequence(
+                expr_stmt.span,
+                expr_stmt.expressions.clone_in(&self.allocator),
+            )));
+            args
+        });
+
+        it.expression = Expression::CallExpression(Box::new_in(call_expr, &self.allocator));
+    }
+
     fn handle_boolean_literal(
         &mut self,
         it: &mut ExpressionStatement<'a>,
@@ -154,7 +391,7 @@ impl<'a> AstReplacer<'a> {
             let mut args = self.ast_builder.vec();
             args.push(Argument::from(
                 self.ast_builder
-                    .expression_boolean_literal(SPAN, expr_stmt.value.clone()),
+                    .expression_boolean_literal(expr_stmt.span, expr_stmt.value.clone()),
             ));
             args
         });
@@ -171,7 +408,7 @@ impl<'a> AstReplacer<'a> {
         let call_expr = self.create_debug_call(line, {
             let mut args = self.ast_builder.vec();
             args.push(Argument::from(self.ast_builder.expression_string_literal(
-                SPAN,
+                expr_stmt.span,
                 expr_stmt.value.clone(),
                 expr_stmt.raw.clone(),
             )));
@@ -190,7 +427,7 @@ impl<'a> AstReplacer<'a> {
         let call_expr = self.create_debug_call(line, {
             let mut args = self.ast_builder.vec();
             args.push(Argument::from(self.ast_builder.expression_numeric_literal(
-                SPAN,
+                expr_stmt.span,
                 expr_stmt.value,
                 expr_stmt.raw,
                 NumberBase::Decimal,
@@ -212,7 +449,7 @@ impl<'a> AstReplacer<'a> {
             let mut args = self.ast_builder.vec();
             args.push(Argument::from(
                 self.ast_builder
-                    .expression_identifier_reference(SPAN, expr_stmt.name.clone()),
+                    .expression_identifier_reference(expr_stmt.span, expr_stmt.name.clone()),
             ));
             args
         });
@@ -227,51 +464,47 @@ impl<'a> AstReplacer<'a> {
     ) {
         let new_call = self.create_debug_call(line, {
             let mut items = self.ast_builder.vec();
-
-            let mut current_object = expr_stmt;
-            let mut full_expression = Vec::new();
-
-            loop {
-                full_expression.push(current_object.property.name.as_str());
-
-                if let Expression::StaticMemberExpression(inner_expr) = &current_object.object {
-                    current_object = inner_expr;
-                } else if let Expression::Identifier(identifier) = &current_object.object {
-                    full_expression.push(&identifier.name);
-                    break;
-                } else {
-                    break;
-                }
-            }
-
-            let full_expression_str = full_expression
-                .into_iter()
-                .rev()
-                .collect::<Vec<_>>()
-                .join(".");
-
-            items.push(Argument::from(
-                self.ast_builder
-                    .expression_identifier_reference(SPAN, full_expression_str),
-            ));
-
+            items.push(Argument::from(Expression::StaticMemberExpression(Box::new_in(
+                expr_stmt.clone_in(&self.allocator),
+                &self.allocator,
+            ))));
             items
         });
 
         it.expression = Expression::CallExpression(Box::new_in(new_call, &self.allocator));
     }
 
+    /// Reports the value of an expression statement without a dedicated
+    /// handler.
+    fn handle_expression(&mut self, it: &mut ExpressionStatement<'a>, line: usize) {
+        let expression = self.ast_builder.move_expression(&mut it.expression);
+        let call_expr = self.create_debug_call(line, {
+            let mut args = self.ast_builder.vec();
+            args.push(Argument::from(expression));
+            args
+        });
+        it.expression = Expression::CallExpression(Box::new_in(call_expr, &self.allocator));
+    }
+
     fn handle_call_expression(
         &mut self,
         it: &mut ExpressionStatement<'a>,
         call_expr: &CallExpression<'a>,
         line: usize,
     ) {
-        // if let Expression::Identifier(identifier) = &call_expr.callee {
-        //     if identifier.name == "setTimeout" {
-        //         return;
-        //     }
-        // }
+        // Timer ids are noise in the results, the callbacks themselves are
+        // still instrumented when the statement is walked.
+        if let Expression::Identifier(identifier) = &call_expr.callee {
+            if TIMER_FUNCTIONS.contains(&identifier.name.as_str()) {
+                return;
+            }
+        }
+        // Tests, assertions and benchmarks report through their own helpers.
+        if root_callee(&call_expr.callee)
+            .is_some_and(|identifier| self.helper(identifier).is_some())
+        {
+            return;
+        }
         let new_expr =
             self.create_debug_call(line, {
                 let mut items = self.ast_builder.vec();
@@ -282,7 +515,7 @@ impl<'a> AstReplacer<'a> {
                     if let Expression::Identifier(identifier) = object {
         
                         if identifier.name == "console" {
-                            
+                            self.pending_site.set((it.span, ResultKind::Log));
                             let mut new_arguments = self.ast_builder.vec();
 
                             for arg in &call_expr.arguments {
@@ -302,7 +535,7 @@ impl<'a> AstReplacer<'a> {
 
                 items.push(Argument::from(
                     self.ast_builder.expression_call(
-                        SPAN,
+                        call_expr.span,
                         call_expr.callee.clone_in(&self.allocator),
                         call_expr.type_parameters.clone_in(&self.allocator),
                         call_expr.arguments.clone_in(&self.allocator),
@@ -316,6 +549,186 @@ impl<'a> AstReplacer<'a> {
         it.expression = Expression::CallExpression(Box::new_in(new_expr, &self.allocator));
     }
 
+    /// Builds one `Xtal(line, ...bindings)` statement per initialized
+    /// declarator. The statements are inserted after the declaration, so the
+    /// bindings are read once they are initialized and `const`/`let` scoping
+    /// and TDZ behavior stay untouched.
+    fn handle_variable_declaration(
+        &self,
+        declaration: &VariableDeclaration<'a>,
+    ) -> std::vec::Vec<Statement<'a>> {
+        let mut statements = std::vec::Vec::new();
+
+        if declaration.declare {
+            return statements;
+        }
+
+        for declarator in &declaration.declarations {
+            if declarator.init.is_none() {
+                continue;
+            }
+
+            let mut names = std::vec::Vec::new();
+            collect_binding_names(&declarator.id, &mut names);
+            if names.is_empty() {
+                continue;
+            }
+
+            let line = self.line_index.line(declarator.span.start as usize);
+            self.pending_site.set((declarator.span, ResultKind::Declaration));
+            let call_expr = self.create_debug_call(line, {
+                let mut args = self.ast_builder.vec();
+                for (name, span) in names {
+                    args.push(Argument::from(
+                        self.ast_builder.expression_identifier_reference(span, name),
+                    ));
+                }
+                args
+            });
+
+            statements.push(self.ast_builder.statement_expression(
+                declarator.span,
+                Expression::CallExpression(Box::new_in(call_expr, &self.allocator)),
+            ));
+        }
+
+        statements
+    }
+
+    /// The helper a reference to a global `test`, `expect` or `bench` is
+    /// rewritten to.
+    fn helper(&self, identifier: &IdentifierReference) -> Option<&'static str> {
+        if !self.helper_references.contains(&identifier.span) {
+            return None;
+        }
+        HELPER_FUNCTIONS
+            .iter()
+            .find(|(function, _)| identifier.name == *function)
+            .map(|(_, helper)| *helper)
+    }
+
+    /// Inserts `XtalLoop(line, start)` at the start of a loop's body.
+    fn guard_loop_body(&self, body: &mut Statement<'a>, loop_span: Span) {
+        let guard = self.create_loop_call("XtalLoop", loop_span);
+        self.prepend_to_body(body, guard);
+    }
+
+    /// Turns a loop or `if` body into a block if it isn't one, so statements
+    /// can be added to it.
+    fn make_block(&self, body: &mut Statement<'a>) {
+        if matches!(body, Statement::BlockStatement(_)) {
+            return;
+        }
+
+        let body_span = body.span();
+        let inner = std::mem::replace(body, self.ast_builder.statement_empty(SPAN));
+        let mut statements = self.ast_builder.vec();
+        statements.push(inner);
+        *body = self.ast_builder.statement_block(body_span, statements);
+    }
+
+    fn prepend_to_body(&self, body: &mut Statement<'a>, statement: Statement<'a>) {
+        self.make_block(body);
+        if let Statement::BlockStatement(block) = body {
+            block.body.insert(0, statement);
+        }
+    }
+
+    /// Loop bodies become blocks before they are walked, so a single
+    /// statement body is counted like any other statement.
+    fn cover_loop_body(&self, body: &mut Statement<'a>) {
+        if self.options.coverage {
+            self.make_block(body);
+        }
+    }
+
+    /// Registers a coverage point for `span` and builds the `XtalCov(id)`
+    /// call that counts it.
+    fn create_coverage_call(&self, span: Span, kind: CoverageKind) -> Expression<'a> {
+        let id = {
+            let mut coverage = self.coverage.borrow_mut();
+            coverage.push(CoveragePoint { span, kind });
+            coverage.len() - 1
+        };
+
+        let mut args = self.ast_builder.vec();
+        args.push(Argument::from(self.ast_builder.expression_numeric_literal(
+            SPAN,
+            id as f64,
+            id.to_string(),
+            NumberBase::Decimal,
+        )));
+        Expression::CallExpression(Box::new_in(
+            self.ast_builder.call_expression(
+                span,
+                self.ast_builder.expression_identifier_reference(span, "XtalCov"),
+                NONE,
+                args,
+                false,
+            ),
+            &self.allocator,
+        ))
+    }
+
+    /// Counts the branch at the start of an `if` consequent or alternate.
+    fn cover_body(&self, body: &mut Statement<'a>) {
+        let counter = self.create_coverage_call(body.span(), CoverageKind::Branch);
+        let statement = self.ast_builder.statement_expression(body.span(), counter);
+        self.prepend_to_body(body, statement);
+    }
+
+    /// Counts the body of `x => x * 2` as the statement it stands for.
+    fn cover_concise_body(&self, arrow: &mut ArrowFunctionExpression<'a>) {
+        if !self.options.coverage {
+            return;
+        }
+        if let Some(Statement::ExpressionStatement(statement)) = arrow.body.statements.first_mut() {
+            self.cover_expression(&mut statement.expression, CoverageKind::Statement);
+        }
+    }
+
+    /// Rewrites `expression` to `(XtalCov(id), expression)`, so it is counted
+    /// every time it is evaluated.
+    fn cover_expression(&self, expression: &mut Expression<'a>, kind: CoverageKind) {
+        let span = expression.span();
+        let counter = self.create_coverage_call(span, kind);
+        let inner = self.ast_builder.move_expression(expression);
+        let mut expressions = self.ast_builder.vec();
+        expressions.push(counter);
+        expressions.push(inner);
+        *expression = self.ast_builder.expression_parenthesized(
+            span,
+            self.ast_builder.expression_sequence(span, expressions),
+        );
+    }
+
+    /// Builds `callee(line, start)` for the loop at `loop_span`. The call
+    /// carries the loop's span, so the error thrown by a guard points at it.
+    fn create_loop_call(&self, callee: &'static str, loop_span: Span) -> Statement<'a> {
+        let line = self.line_index.line(loop_span.start as usize);
+        let mut args = self.ast_builder.vec();
+        for value in [line, loop_span.start as usize] {
+            args.push(Argument::from(self.ast_builder.expression_numeric_literal(
+                SPAN,
+                value as f64,
+                value.to_string(),
+                NumberBase::Decimal,
+            )));
+        }
+
+        let call_expr = self.ast_builder.call_expression(
+            loop_span,
+            self.ast_builder.expression_identifier_reference(loop_span, callee),
+            NONE,
+            args,
+            false,
+        );
+        self.ast_builder.statement_expression(
+            loop_span,
+            Expression::CallExpression(Box::new_in(call_expr, &self.allocator)),
+        )
+    }
+
     fn create_debug_call(
         &self,
         line: usize,
@@ -323,20 +736,53 @@ impl<'a> AstReplacer<'a> {
     ) -> CallExpression<'a> {
         let mut args = self.ast_builder.vec();
 
+        let (span, kind) = self.pending_site.get();
+        self.sites.borrow_mut().push(Site { line, span, kind });
+        // Timed calls read the clock through `XtalStart` before their
+        // arguments are evaluated, `XtalTimed` reads it again once they are.
+        // Declarations are reported after they ran, so there is nothing to
+        // time.
+        let timed = self.options.timing && kind != ResultKind::Declaration;
+
         args.push(Argument::from(self.ast_builder.expression_numeric_literal(
             SPAN,
             line as f64,
             line.to_string(),
             NumberBase::Decimal,
         )));
+        // The original span goes along with the line, so results can be
+        // attached to the exact expression they came from.
+        for offset in [span.start, span.end] {
+            args.push(Argument::from(self.ast_builder.expression_numeric_literal(
+                SPAN,
+                offset as f64,
+                offset.to_string(),
+                NumberBase::Decimal,
+            )));
+        }
+        if timed {
+            args.push(Argument::from(Expression::CallExpression(Box::new_in(
+                self.ast_builder.call_expression(
+                    SPAN,
+                    self.ast_builder.expression_identifier_reference(SPAN, "XtalStart"),
+                    NONE,
+                    self.ast_builder.vec(),
+                    false,
+                ),
+                &self.allocator,
+            ))));
+        }
         for arg in additional_args {
             args.push(Argument::from(arg));
         }
 
+        // The call carries the span of the code it reports on, so positions
+        // inside the generated program map back to the user's source.
+        let callee = if timed { "XtalTimed" } else { "Xtal" };
         self.ast_builder.call_expression(
-            SPAN,
+            span,
             self.ast_builder
-                .expression_identifier_reference(SPAN, "Xtal"),
+                .expression_identifier_reference(span, callee),
             NONE,
             args,
             false,
@@ -344,10 +790,262 @@ impl<'a> AstReplacer<'a> {
     }
 }
 
+/// The function at the root of a call chain, `expect` for
+/// `expect(a).not.toBe(b)`.
+fn root_callee<'b, 'a>(callee: &'b Expression<'a>) -> Option<&'b IdentifierReference<'a>> {
+    match callee {
+        Expression::Identifier(identifier) => Some(identifier),
+        Expression::StaticMemberExpression(member) => match &member.object {
+            Expression::CallExpression(call) => root_callee(&call.callee),
+            object => root_callee(object),
+        },
+        _ => None,
+    }
+}
+
+/// Whether a statement does anything when it is reached. Hoisted functions,
+/// imports and type declarations don't, so they aren't counted.
+fn is_covered_statement(statement: &Statement) -> bool {
+    match statement {
+        Statement::FunctionDeclaration(_)
+        | Statement::EmptyStatement(_)
+        | Statement::ImportDeclaration(_)
+        | Statement::ExportAllDeclaration(_)
+        | Statement::TSTypeAliasDeclaration(_)
+        | Statement::TSInterfaceDeclaration(_)
+        | Statement::TSModuleDeclaration(_) => false,
+        Statement::VariableDeclaration(declaration) => !declaration.declare,
+        Statement::ClassDeclaration(class) => !class.declare,
+        Statement::ExportNamedDeclaration(export) => match &export.declaration {
+            Some(Declaration::VariableDeclaration(declaration)) => !declaration.declare,
+            Some(Declaration::ClassDeclaration(class)) => !class.declare,
+            _ => false,
+        },
+        _ => true,
+    }
+}
+
+/// Span of the loop a statement is, looking through labels.
+fn loop_span(statement: &Statement) -> Option<Span> {
+    match statement {
+        Statement::ForStatement(it) => Some(it.span),
+        Statement::ForInStatement(it) => Some(it.span),
+        Statement::ForOfStatement(it) => Some(it.span),
+        Statement::WhileStatement(it) => Some(it.span),
+        Statement::DoWhileStatement(it) => Some(it.span),
+        Statement::LabeledStatement(it) => loop_span(&it.body),
+        _ => None,
+    }
+}
+
+/// Collects the names bound by a declarator, including nested destructuring
+/// patterns, in source order.
+fn collect_binding_names<'a>(
+    pattern: &BindingPattern<'a>,
+    names: &mut std::vec::Vec<(Atom<'a>, Span)>,
+) {
+    match &pattern.kind {
+        BindingPatternKind::BindingIdentifier(identifier) => {
+            names.push((identifier.name.clone(), identifier.span))
+        }
+        BindingPatternKind::ObjectPattern(object) => {
+            for property in &object.properties {
+                collect_binding_names(&property.value, names);
+            }
+            if let Some(rest) = &object.rest {
+                collect_binding_names(&rest.argument, names);
+            }
+        }
+        BindingPatternKind::ArrayPattern(array) => {
+            for element in array.elements.iter().flatten() {
+                collect_binding_names(element, names);
+            }
+            if let Some(rest) = &array.rest {
+                collect_binding_names(&rest.argument, names);
+            }
+        }
+        BindingPatternKind::AssignmentPattern(assignment) => {
+            collect_binding_names(&assignment.left, names)
+        }
+    }
+}
+
 impl<'a> VisitMut<'a> for AstReplacer<'a> {
+    fn visit_statements(&mut self, it: &mut oxc_allocator::Vec<'a, Statement<'a>>) {
+        walk_statements(self, it);
+
+        // Report declared bindings right after their declaration. This runs
+        // after the walk so the inserted statements are not wrapped again.
+        let mut index = 0;
+        while index < it.len() {
+            if self.options.coverage && is_covered_statement(&it[index]) {
+                let span = it[index].span();
+                let counter = self.create_coverage_call(span, CoverageKind::Statement);
+                it.insert(index, self.ast_builder.statement_expression(span, counter));
+                index += 1;
+            }
+
+            let declaration = match &it[index] {
+                Statement::VariableDeclaration(declaration) => Some(&**declaration),
+                Statement::ExportNamedDeclaration(export) => match &export.declaration {
+                    Some(Declaration::VariableDeclaration(declaration)) => Some(&**declaration),
+                    _ => None,
+                },
+                _ => None,
+            };
+
+            let reports = declaration
+                .map(|declaration| self.handle_variable_declaration(declaration))
+                .unwrap_or_default();
+
+            index += 1;
+            for report in reports {
+                it.insert(index, report);
+                index += 1;
+            }
+        }
+    }
+
+    fn visit_statement(&mut self, it: &mut Statement<'a>) {
+        let labeled = std::mem::take(&mut self.in_label);
+        walk_statement(self, it);
+
+        // A loop restarts its guard's count every time it is entered, so a
+        // nested loop is counted per run of the outer one. The loop becomes
+        // `{ XtalLoopEnter(line, start); loop }`, a labeled one together with
+        // its label so `continue label` keeps working. Loops are marked even
+        // without an iteration limit, a timeout is reported at the loop that
+        // was running.
+        if labeled {
+            return;
+        }
+        if let Some(span) = loop_span(it) {
+            let loop_statement = self.ast_builder.move_statement(it);
+            let mut statements = self.ast_builder.vec();
+            statements.push(self.create_loop_call("XtalLoopEnter", span));
+            statements.push(loop_statement);
+            *it = self.ast_builder.statement_block(span, statements);
+        }
+    }
+
+    fn visit_labeled_statement(&mut self, it: &mut LabeledStatement<'a>) {
+        self.visit_label_identifier(&mut it.label);
+        self.in_label = true;
+        self.visit_statement(&mut it.body);
+    }
+
+    fn visit_call_expression(&mut self, it: &mut CallExpression<'a>) {
+        walk_call_expression(self, it);
+
+        // `test(name, body)` becomes `XtalTest(line, start, end, name, body)`,
+        // the same goes for `expect` and `bench`.
+        let helper = match &it.callee {
+            Expression::Identifier(identifier) => self.helper(identifier),
+            _ => None,
+        };
+        let Some(helper) = helper else {
+            return;
+        };
+
+        let line = self.line_index.line(it.span.start as usize);
+        let callee_span = it.callee.span();
+        it.callee = self.ast_builder.expression_identifier_reference(callee_span, helper);
+        for (index, value) in [line, it.span.start as usize, it.span.end as usize]
+            .into_iter()
+            .enumerate()
+        {
+            it.arguments.insert(
+                index,
+                Argument::from(self.ast_builder.expression_numeric_literal(
+                    SPAN,
+                    value as f64,
+                    value.to_string(),
+                    NumberBase::Decimal,
+                )),
+            );
+        }
+    }
+
+    fn visit_if_statement(&mut self, it: &mut IfStatement<'a>) {
+        walk_if_statement(self, it);
+        if !self.options.coverage {
+            return;
+        }
+
+        // A single statement body isn't in a statement list, so it is only
+        // counted by its branch, which covers the same span.
+        self.cover_body(&mut it.consequent);
+        if let Some(alternate) = &mut it.alternate {
+            self.cover_body(alternate);
+        }
+    }
+
+    fn visit_conditional_expression(&mut self, it: &mut ConditionalExpression<'a>) {
+        walk_conditional_expression(self, it);
+        if self.options.coverage {
+            self.cover_expression(&mut it.consequent, CoverageKind::Branch);
+            self.cover_expression(&mut it.alternate, CoverageKind::Branch);
+        }
+    }
+
+    fn visit_logical_expression(&mut self, it: &mut LogicalExpression<'a>) {
+        walk_logical_expression(self, it);
+        // The left operand always runs, only the right one is a branch.
+        if self.options.coverage {
+            self.cover_expression(&mut it.right, CoverageKind::Branch);
+        }
+    }
+
+    fn visit_arrow_function_expression(&mut self, it: &mut ArrowFunctionExpression<'a>) {
+        if !it.expression {
+            walk_arrow_function_expression(self, it);
+            return;
+        }
+
+        // The body of `x => x * 2` is stored as an expression statement, but
+        // it is the return value. Only the expression is walked, so it isn't
+        // wrapped into an `Xtal` call that returns `undefined`.
+        self.visit_formal_parameters(&mut it.params);
+        if let Some(Statement::ExpressionStatement(statement)) = it.body.statements.first_mut() {
+            self.visit_expression(&mut statement.expression);
+        }
+        self.cover_concise_body(it);
+    }
+
+    fn visit_for_statement(&mut self, it: &mut ForStatement<'a>) {
+        self.cover_loop_body(&mut it.body);
+        walk_for_statement(self, it);
+        self.guard_loop_body(&mut it.body, it.span);
+    }
+
+    fn visit_for_in_statement(&mut self, it: &mut ForInStatement<'a>) {
+        self.cover_loop_body(&mut it.body);
+        walk_for_in_statement(self, it);
+        self.guard_loop_body(&mut it.body, it.span);
+    }
+
+    fn visit_for_of_statement(&mut self, it: &mut ForOfStatement<'a>) {
+        self.cover_loop_body(&mut it.body);
+        walk_for_of_statement(self, it);
+        self.guard_loop_body(&mut it.body, it.span);
+    }
+
+    fn visit_while_statement(&mut self, it: &mut WhileStatement<'a>) {
+        self.cover_loop_body(&mut it.body);
+        walk_while_statement(self, it);
+        self.guard_loop_body(&mut it.body, it.span);
+    }
+
+    fn visit_do_while_statement(&mut self, it: &mut DoWhileStatement<'a>) {
+        self.cover_loop_body(&mut it.body);
+        walk_do_while_statement(self, it);
+        self.guard_loop_body(&mut it.body, it.span);
+    }
+
     fn visit_expression_statement(&mut self, it: &mut ExpressionStatement<'a>) {
-        let line = get_line_number(&self.source_text, it.span.start.try_into().unwrap());
+        let line = self.line_index.line(it.span.start as usize);
         let expression = &it.expression.clone_in(&self.allocator);
+        self.pending_site.set((it.span, ResultKind::Expression));
 
         match expression {
             Expression::ConditionalExpression(expr) => self.handle_conditional_expression(it, expr, line),
@@ -363,23 +1061,28 @@ impl<'a> VisitMut<'a> for AstReplacer<'a> {
             Expression::StaticMemberExpression(expr) => {
                 self.handle_static_member_expression(it, expr, line)
             }
+            Expression::AssignmentExpression(expr) => {
+                self.handle_assignment_expression(it, expr, line)
+            }
+            Expression::UpdateExpression(expr) => self.handle_update_expression(it, expr, line),
+            Expression::UnaryExpression(expr) => self.handle_unary_expression(it, expr, line),
+            Expression::AwaitExpression(expr) => self.handle_await_expression(it, expr, line),
+            Expression::SequenceExpression(expr) => {
+                self.handle_sequence_expression(it, expr, line)
+            }
             Expression::BooleanLiteral(expr) => self.handle_boolean_literal(it, expr, line),
             Expression::StringLiteral(expr) => self.handle_string_literal(it, expr, line),
             Expression::NumericLiteral(expr) => self.handle_numeric_literal(it, expr, line),
             Expression::Identifier(expr) => self.handle_identifier(it, expr, line),
             Expression::CallExpression(expr) => self.handle_call_expression(it, expr, line),
-            _ => {}
-        }
-
-        if let Expression::CallExpression(call_expr) = &it.expression {
-            if let Expression::Identifier(identifier) = &call_expr.callee {
-                if identifier.name == "Xtal" {
-                    // Already wrapped, no need to wrap again
-                    return;
-                }
-            }
+            // JSX elements, TypeScript's `x!` and `x as T` and everything
+            // else is reported as is.
+            _ => self.handle_expression(it, line),
         }
 
+        // Walk into the (possibly wrapped) statement so callbacks passed to
+        // timers and promises report their values as well. Only expression
+        // statements are wrapped, so the Xtal call itself is never re-wrapped.
         walk_expression_statement(self, it);
     }
 }
@@ -0,0 +1,89 @@
+/// Line starts of a snippet, built once so offset lookups are a binary search
+/// instead of a scan from the top of the source for every expression.
+///
+/// `\n`, `\r\n` and a lone `\r` end a line, the same as in the editor. Columns
+/// are 1-indexed and counted in UTF-16 code units, which is what Monaco, V8
+/// stack traces and source maps use.
+#[derive(Debug, Clone)]
+pub struct LineIndex {
+    source_text: String,
+    // Byte offset every line starts at, the first line starts after a BOM.
+    line_starts: Vec<usize>,
+}
+
+impl LineIndex {
+    pub fn new(source_text: &str) -> Self {
+        let bytes = source_text.as_bytes();
+        let first_line_start = if source_text.starts_with('\u{FEFF}') {
+            '\u{FEFF}'.len_utf8()
+        } else {
+            0
+        };
+
+        let mut line_starts = vec![first_line_start];
+        for (i, byte) in bytes.iter().enumerate().skip(first_line_start) {
+            match byte {
+                b'\n' => line_starts.push(i + 1),
+                b'\r' if bytes.get(i + 1) != Some(&b'\n') => line_starts.push(i + 1),
+                _ => {}
+            }
+        }
+
+        Self {
+            source_text: source_text.to_string(),
+            line_starts,
+        }
+    }
+
+    pub fn source_text(&self) -> &str {
+        &self.source_text
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// 1-indexed line of a byte offset. Offsets past the end belong to the last line.
+    pub fn line(&self, offset: usize) -> usize {
+        self.line_starts
+            .partition_point(|&start| start <= offset)
+            .max(1)
+    }
+
+    /// 1-indexed column of a byte offset.
+    pub fn column(&self, offset: usize) -> usize {
+        let offset = self.clamp(offset);
+        let line_start = self.line_starts[self.line(offset) - 1];
+        if offset <= line_start {
+            return 1;
+        }
+        self.source_text[line_start..offset].encode_utf16().count() + 1
+    }
+
+    /// Byte offset of a 1-indexed line and column. Columns past the end of the
+    /// line resolve to the line break.
+    pub fn offset(&self, line: usize, column: usize) -> usize {
+        let Some(&line_start) = self.line_starts.get(line.saturating_sub(1)) else {
+            return self.source_text.len();
+        };
+
+        let mut units = column.saturating_sub(1);
+        let mut offset = line_start;
+        for c in self.source_text[line_start..].chars() {
+            if units == 0 || c == '\n' || c == '\r' {
+                break;
+            }
+            units = units.saturating_sub(c.len_utf16());
+            offset += c.len_utf8();
+        }
+        offset
+    }
+
+    fn clamp(&self, offset: usize) -> usize {
+        let mut offset = offset.min(self.source_text.len());
+        while !self.source_text.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        offset
+    }
+}
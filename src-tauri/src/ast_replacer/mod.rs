@@ -1,2 +1,4 @@
+pub mod inspect;
 pub mod lib;
+pub mod line_index;
 pub mod utils;
\ No newline at end of file
@@ -1,70 +1,344 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
-pub fn get_line_number(source_text: &str, offset: usize) -> usize {
-    // Split by lines and calculate cumulative byte lengths to pinpoint the correct line
-    let mut cumulative_offset = 0;
+use crate::diagnostic::Diagnostic;
+
+use super::inspect::Inspected;
+use super::lib::{CoveragePoint, Site};
+use super::line_index::LineIndex;
+
+/// How many of the latest values are kept per expression, earlier hits only count
+/// towards `hits`.
+pub const LAST_VALUES: usize = 5;
+
+/// What produced a reported value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResultKind {
+    Expression,
+    Log,
+    Declaration,
+}
+
+/// What a coverage range counts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CoverageKind {
+    Statement,
+    // A side of an `if`, a conditional or a logical expression.
+    Branch,
+}
+
+/// How often a statement or branch of the snippet ran. Ranges with no hits
+/// are dead code for this run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CoverageRange {
+    pub line: usize,
+    pub column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+    pub start: usize,
+    pub end: usize,
+    pub kind: CoverageKind,
+    pub hits: usize,
+}
+
+/// One value reported by the snippet, persisted on the tab for the frontend.
+/// `start` and `end` are the byte offsets of the reporting expression, they
+/// tell apart several expressions on one line.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResultEntry {
+    pub line: usize,
+    pub column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+    pub start: usize,
+    pub end: usize,
+    pub kind: ResultKind,
+    pub value: Inspected,
+    // 1-based index of the run of the expression that produced the value.
+    pub hit: usize,
+    // Milliseconds the expression took, only measured in timing mode.
+    #[serde(default)]
+    pub duration: Option<f64>,
+}
+
+/// Results of a single instrumented expression. Expressions inside loops and
+/// callbacks run many times, every run is one hit.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LineHits {
+    pub line: usize,
+    pub column: usize,
+    pub start: usize,
+    pub end: usize,
+    pub hits: usize,
+    // Rendered values of the last hits, oldest first.
+    pub last_values: Vec<String>,
+    // Milliseconds the last hit took, in timing mode.
+    #[serde(default)]
+    pub duration: Option<f64>,
+}
 
-    for (i, line) in source_text.lines().enumerate() {
-        cumulative_offset += line.len() + 1; // Add 1 for the newline character
-        if offset < cumulative_offset {
-            return i + 1; // Lines are 1-indexed
+/// Outcome of a `test(name, body)` block in the snippet.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TestResult {
+    pub name: String,
+    pub line: usize,
+    pub column: usize,
+    pub start: usize,
+    pub end: usize,
+    pub passed: bool,
+    pub failure: Option<TestFailure>,
+}
+
+/// The assertion or exception a test failed on.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TestFailure {
+    pub line: usize,
+    pub column: usize,
+    pub start: usize,
+    pub message: String,
+}
+
+/// Turns the values `XtalCollect` returns into result entries, keeping the
+/// values of the last `LAST_VALUES` hits of every expression.
+pub fn collect_results(
+    debug_results: Vec<Value>,
+    sites: &[Site],
+    line_index: &LineIndex,
+) -> Vec<ResultEntry> {
+    let field = |item: &Value, name: &str| item.get(name).and_then(|v| v.as_u64()).map(|v| v as usize);
+    let kinds = sites
+        .iter()
+        .map(|site| ((site.span.start as usize, site.span.end as usize), site.kind))
+        .collect::<HashMap<_, _>>();
+
+    // (start, end) -> (line, hit -> reported values and their durations)
+    let mut grouped: BTreeMap<
+        (usize, usize),
+        (usize, BTreeMap<usize, Vec<(Inspected, Option<f64>)>>),
+    > = BTreeMap::new();
+
+    for mut item in debug_results {
+        let (Some(line), Some(start), Some(end)) =
+            (field(&item, "line"), field(&item, "start"), field(&item, "end"))
+        else {
+            continue;
+        };
+        // A value the backend can't read is skipped, it never drops the rest.
+        let Some(value) = item
+            .get_mut("value")
+            .map(Value::take)
+            .and_then(|value| serde_json::from_value::<Inspected>(value).ok())
+        else {
+            continue;
+        };
+        let hit = field(&item, "hit").unwrap_or(1);
+        let duration = item.get("duration").and_then(Value::as_f64);
+
+        let (_, values) = grouped.entry((start, end)).or_insert_with(|| (line, BTreeMap::new()));
+        values.entry(hit).or_default().push((value, duration));
+
+        // Promises can resolve out of order, so the oldest hit is dropped
+        // rather than the one that arrived first.
+        if values.len() > LAST_VALUES {
+            values.pop_first();
         }
     }
 
-    // Fallback: If offset exceeds the total length
-    source_text.lines().count()
-}
+    let mut entries = Vec::new();
+    for ((start, end), (line, hits)) in grouped {
+        let kind = kinds.get(&(start, end)).copied().unwrap_or(ResultKind::Expression);
+        let column = line_index.column(start);
+        let end_line = line_index.line(end);
+        let end_column = line_index.column(end);
 
-pub fn transform_to_result(mut debug_results: Vec<Value>) -> String {
-    let mut result = String::new();
-    let mut current_line = 0; // Tracks the last line position
+        for (hit, values) in hits {
+            for (value, duration) in values {
+                entries.push(ResultEntry {
+                    line,
+                    column,
+                    end_line,
+                    end_column,
+                    start,
+                    end,
+                    kind,
+                    value,
+                    hit,
+                    duration,
+                });
+            }
+        }
+    }
+
+    entries.sort_by_key(|entry| (entry.line, entry.start, entry.end, entry.hit));
+    entries
+}
 
+/// Turns the raw `XtalTests` into test results, located in the user's source.
+pub fn collect_tests(debug_tests: Vec<Value>, line_index: &LineIndex) -> Vec<TestResult> {
+    #[derive(Deserialize)]
+    struct RawTest {
+        name: String,
+        start: usize,
+        end: usize,
+        passed: bool,
+        failure: Option<RawFailure>,
+    }
 
-    // Sort by line
-    debug_results.sort_by(|a, b| {
-        let line_a = a.get("line").and_then(|l| l.as_u64()).unwrap_or(0);
-        let line_b = b.get("line").and_then(|l| l.as_u64()).unwrap_or(0);
-        line_a.cmp(&line_b)
-    });
+    #[derive(Deserialize)]
+    struct RawFailure {
+        start: usize,
+        message: String,
+    }
 
-    for (i, item) in debug_results.iter().enumerate() {
-        if let Some(line) = item.get("line").and_then(|l| l.as_u64()) {
-            let target_line = line as usize;
+    debug_tests
+        .into_iter()
+        .filter_map(|test| serde_json::from_value::<RawTest>(test).ok())
+        .map(|test| TestResult {
+            name: test.name,
+            line: line_index.line(test.start),
+            column: line_index.column(test.start),
+            start: test.start,
+            end: test.end,
+            passed: test.passed,
+            failure: test.failure.map(|failure| TestFailure {
+                line: line_index.line(failure.start),
+                column: line_index.column(failure.start),
+                start: failure.start,
+                message: failure.message,
+            }),
+        })
+        .collect()
+}
 
-            // Add exact newlines to move to the correct line
-            while current_line < target_line - 1 {
-                result.push('\n');
-                current_line += 1;
+/// Pairs the coverage points of the instrumented snippet with the counts
+/// `XtalCoverage` collected, points that never ran have no count.
+pub fn collect_coverage(
+    points: &[CoveragePoint],
+    counts: &[usize],
+    line_index: &LineIndex,
+) -> Vec<CoverageRange> {
+    let mut ranges = points
+        .iter()
+        .enumerate()
+        .map(|(id, point)| {
+            let (start, end) = (point.span.start as usize, point.span.end as usize);
+            CoverageRange {
+                line: line_index.line(start),
+                column: line_index.column(start),
+                end_line: line_index.line(end),
+                end_column: line_index.column(end),
+                start,
+                end,
+                kind: point.kind,
+                hits: counts.get(id).copied().unwrap_or_default(),
             }
+        })
+        .collect::<Vec<_>>();
+    ranges.sort_by_key(|range| (range.start, std::cmp::Reverse(range.end)));
+    ranges
+}
+
+pub fn collect_line_hits(entries: &[ResultEntry]) -> Vec<LineHits> {
+    // (line, start, end) -> (column, hit count, hit -> rendered values)
+    let mut grouped: BTreeMap<(usize, usize, usize), (usize, usize, BTreeMap<usize, Vec<String>>)> =
+        BTreeMap::new();
+    // Duration of the latest hit of every expression.
+    let mut durations: HashMap<(usize, usize, usize), (usize, f64)> = HashMap::new();
+
+    for entry in entries {
+        let key = (entry.line, entry.start, entry.end);
+        let (_, hits, values) = grouped
+            .entry(key)
+            .or_insert_with(|| (entry.column, 0, BTreeMap::new()));
+        *hits = (*hits).max(entry.hit);
+        values.entry(entry.hit).or_default().push(entry.value.render());
 
-            if current_line == target_line && !result.is_empty() {
-                result.push(' ');
+        if let Some(duration) = entry.duration {
+            let latest = durations.entry(key).or_insert((entry.hit, duration));
+            if entry.hit >= latest.0 {
+                *latest = (entry.hit, duration);
             }
+        }
+    }
 
-            // Append the value if it exists
-            if let Some(value) = item.get("value") {
-                let value_str = value_to_string(value);
-                result.push_str(&value_str);
-
-                // Only add a newline if it's the last value on this line
-                if i + 1 < debug_results.len() {
-                    if let Some(next_line) = debug_results[i + 1].get("line").and_then(|l| l.as_u64()) {
-                        if next_line as usize != target_line {
-                            result.push('\n');
-                        }
-                    }
-                } else {
-                    result.push('\n'); // Final value, ensure newline
-                }
+    grouped
+        .into_iter()
+        .map(|(key, (column, hits, values))| {
+            let (line, start, end) = key;
+            LineHits {
+                line,
+                column,
+                start,
+                end,
+                hits,
+                last_values: values.into_values().map(|values| values.join(" ")).collect(),
+                duration: durations.get(&key).map(|(_, duration)| *duration),
             }
+        })
+        .collect()
+}
 
-            current_line = target_line; // Update the current line after appending value
+/// Renders the results column, one row per source line. Sites that ran more
+/// than once are collapsed to their hit count and last value, errors are
+/// shown on the line that threw after the values reported before it.
+pub fn transform_to_result(line_hits: &[LineHits], diagnostics: &[Diagnostic]) -> String {
+    let mut lines: BTreeMap<usize, Vec<String>> = BTreeMap::new();
+    for hits in line_hits {
+        let Some(last) = hits.last_values.last() else {
+            continue;
+        };
+        let mut rendered = if hits.hits > 1 {
+            format!("×{}, last: {}", hits.hits, last)
+        } else {
+            last.clone()
+        };
+        if let Some(duration) = hits.duration {
+            rendered.push_str(&format!(" ({})", format_duration(duration)));
         }
+        lines.entry(hits.line).or_default().push(rendered);
+    }
+    for diagnostic in diagnostics {
+        if let Some(span) = &diagnostic.span {
+            lines
+                .entry(span.line)
+                .or_default()
+                .push(format!("✖ {}", diagnostic.message));
+        }
+    }
+
+    let mut result = String::new();
+    let mut current_line = 1;
+
+    for (line, values) in lines {
+        // Add exact newlines to move to the correct line
+        while current_line < line {
+            result.push('\n');
+            current_line += 1;
+        }
+        result.push_str(&values.join(" "));
+    }
+
+    if !result.is_empty() {
+        result.push('\n');
     }
 
     result
 }
 
+/// Renders a duration in milliseconds, sub-millisecond ones in microseconds.
+pub fn format_duration(ms: f64) -> String {
+    if ms < 1.0 {
+        format!("{:.1}µs", ms * 1000.0)
+    } else {
+        format!("{:.2}ms", ms)
+    }
+}
+
+/// Renders plain JSON, used for JSON scratchpads.
 pub fn value_to_string(value: &Value) -> String {
     match value {
         Value::Null => "null".to_string(),
@@ -0,0 +1,438 @@
+// XtalInspect turns a value into the tagged representation the backend
+// renders (see ast_replacer/inspect.rs), so values JSON can't carry survive.
+// Cycles, deep nesting and long collections are cut off here, before the
+// value has to be serialized.
+globalThis.XtalInspectOptions = { depth: 2, maxArrayLength: 100, maxStringLength: 10000 };
+
+const xtalConstructorName = (value) => {
+    const proto = Object.getPrototypeOf(value);
+    return proto === null ? null : (proto.constructor?.name ?? 'Object');
+};
+
+const xtalInspectObject = (value, inspect) => {
+    const { maxArrayLength } = globalThis.XtalInspectOptions;
+    const take = (iterable, map) => {
+        const items = [];
+        let more = 0;
+        for (const item of iterable) {
+            if (items.length < maxArrayLength) {
+                items.push(map(item));
+            } else {
+                more += 1;
+            }
+        }
+        return [items, more];
+    };
+
+    if (Array.isArray(value)) {
+        const length = Math.min(value.length, maxArrayLength);
+        const items = [];
+        for (let index = 0; index < length; index += 1) {
+            items.push(inspect(value[index]));
+        }
+        return { type: 'array', items, more: value.length - length };
+    }
+    if (value instanceof Map) {
+        const [entries, more] = take(value, ([key, entry]) => [inspect(key), inspect(entry)]);
+        return { type: 'map', entries, more };
+    }
+    if (value instanceof Set) {
+        const [values, more] = take(value, inspect);
+        return { type: 'set', values, more };
+    }
+    if (value instanceof Date) {
+        return { type: 'date', value: isNaN(value) ? 'Invalid Date' : value.toISOString() };
+    }
+    if (value instanceof RegExp) {
+        return { type: 'regexp', value: String(value) };
+    }
+    if (ArrayBuffer.isView(value) && !(value instanceof DataView)) {
+        const [items, more] = take(value, String);
+        return { type: 'typedarray', constructor: value.constructor.name, items, more };
+    }
+    if (value instanceof Error) {
+        return { type: 'error', name: value.name, message: value.message };
+    }
+
+    return {
+        type: 'object',
+        constructor: xtalConstructorName(value),
+        entries: Object.keys(value).map((key) => {
+            try {
+                return [key, inspect(value[key])];
+            } catch (error) {
+                return [key, { type: 'error', name: 'Getter', message: String(error) }];
+            }
+        }),
+    };
+};
+
+globalThis.XtalInspect = (root) => {
+    const { depth: maxDepth, maxStringLength } = globalThis.XtalInspectOptions;
+    // Objects currently being inspected, and the ids of those that turned
+    // out to be referenced from inside themselves.
+    const stack = [];
+    const refs = new Map();
+
+    const inspect = (value, depth) => {
+        switch (typeof value) {
+            case 'undefined':
+                return { type: 'undefined' };
+            case 'boolean':
+                return { type: 'boolean', value };
+            case 'number':
+                return { type: 'number', value: Object.is(value, -0) ? '-0' : String(value) };
+            case 'bigint':
+                return { type: 'bigint', value: String(value) };
+            case 'string':
+                return value.length > maxStringLength
+                    ? { type: 'string', value: value.slice(0, maxStringLength), more: value.length - maxStringLength }
+                    : { type: 'string', value };
+            case 'symbol':
+                return { type: 'symbol', description: value.description ?? '' };
+            case 'function':
+                return {
+                    type: 'function',
+                    name: value.name,
+                    class: /^class[\s{]/.test(Function.prototype.toString.call(value)),
+                };
+        }
+
+        if (value === null) {
+            return { type: 'null' };
+        }
+        if (stack.includes(value)) {
+            if (!refs.has(value)) {
+                refs.set(value, refs.size + 1);
+            }
+            return { type: 'circular', id: refs.get(value) };
+        }
+        if (depth > maxDepth) {
+            return { type: 'truncated', label: Array.isArray(value) ? 'Array' : (xtalConstructorName(value) ?? 'Object') };
+        }
+
+        stack.push(value);
+        let inspected;
+        try {
+            inspected = xtalInspectObject(value, (child) => inspect(child, depth + 1));
+        } finally {
+            stack.pop();
+        }
+
+        return refs.has(value) ? { type: 'ref', id: refs.get(value), value: inspected } : inspected;
+    };
+
+    try {
+        return inspect(root, 0);
+    } catch (error) {
+        // One value that can't be inspected must not take the other results down.
+        return { type: 'error', name: 'InspectError', message: String(error) };
+    }
+};
+
+// Xtal is the function instrumented expressions report their values through,
+// `start` and `end` are the offsets of the expression in the user's source.
+// Promises are awaited so `await` and `.then` results land on their own line,
+// the rest is recorded synchronously to keep the evaluation order.
+// `hit` counts how many times an expression ran, e.g. once per loop iteration.
+// Every site keeps the values of its last `XtalLastValues` hits only, older
+// ones make room for newer ones. Values past `XtalLimits.maxResults` across
+// all sites are only counted. `XtalLastLine` is the line that last reported
+// a value or ran a loop iteration, a timeout is reported at it.
+globalThis.XtalLimits = { maxResults: 10000, maxLoopIterations: Infinity };
+globalThis.XtalLastValues = 5;
+globalThis.XtalSites = new Map();
+globalThis.XtalHits = new Map();
+globalThis.XtalDropped = 0;
+globalThis.XtalLastLine = null;
+// Values currently kept across all sites.
+let xtalKept = 0;
+// Nothing is reported while a benchmark calls its function.
+let xtalMuted = 0;
+
+const xtalRecord = (line, start, end, hit, value, duration) => {
+    const key = `${start}:${end}`;
+    let site = globalThis.XtalSites.get(key);
+    if (site === undefined) {
+        site = { line, start, end, records: [] };
+        globalThis.XtalSites.set(key, site);
+    }
+
+    // Hits that fell out of the window are dropped, including a promise of
+    // an older hit that resolves late.
+    const oldest = (globalThis.XtalHits.get(key) ?? hit) - globalThis.XtalLastValues;
+    if (hit <= oldest) {
+        return;
+    }
+    const kept = site.records.length;
+    site.records = site.records.filter((record) => record.hit > oldest);
+    xtalKept -= kept - site.records.length;
+
+    // At the limit a site makes room by giving up its own oldest value, only
+    // sites with nothing kept yet lose theirs.
+    if (xtalKept >= globalThis.XtalLimits.maxResults) {
+        if (site.records.length === 0) {
+            globalThis.XtalDropped += 1;
+            return;
+        }
+        site.records.shift();
+        xtalKept -= 1;
+    }
+    site.records.push({ hit, value: XtalInspect(value), duration });
+    xtalKept += 1;
+};
+
+// The kept values of every site, in the shape `collect_results` reads.
+globalThis.XtalCollect = () =>
+    [...globalThis.XtalSites.values()].flatMap(({ line, start, end, records }) =>
+        records.map((record) => ({ line, start, end, ...record })),
+    );
+
+// `started` is the clock reading taken before the values were evaluated, a
+// promise is timed until it resolves. Untimed calls leave it undefined.
+const xtalReport = (line, start, end, started, values) => {
+    if (xtalMuted > 0) {
+        return;
+    }
+    const key = `${start}:${end}`;
+    const hit = (globalThis.XtalHits.get(key) ?? 0) + 1;
+    globalThis.XtalHits.set(key, hit);
+    globalThis.XtalLastLine = line;
+    const elapsed = () => (started === undefined ? undefined : XtalNow() - started);
+
+    values.forEach((value) => {
+        if (value !== null && typeof value?.then === 'function') {
+            // Rejections are passed on, so a promise nobody handles still
+            // fails the run as an unhandled rejection.
+            Promise.resolve(value).then(
+                (resolved) => xtalRecord(line, start, end, hit, resolved, elapsed()),
+                (error) => {
+                    throw error;
+                },
+            );
+        } else {
+            xtalRecord(line, start, end, hit, value, elapsed());
+        }
+    });
+};
+
+globalThis.Xtal = (line, start, end, ...values) => xtalReport(line, start, end, undefined, values);
+
+// Timing mode, see `AstReplacerOptions::timing`. `XtalNow` is the backend's
+// clock in milliseconds, registered with the runtime.
+globalThis.XtalNow = () => rustyscript.functions.xtal_now();
+// The clock reading a timed call starts with. Reading the clock goes through
+// the backend, a benchmark skips it so it times the snippet's code only.
+globalThis.XtalStart = () => (xtalMuted > 0 ? undefined : XtalNow());
+globalThis.XtalTimed = (line, start, end, started, ...values) =>
+    xtalReport(line, start, end, started, values);
+
+// Loop guards, keyed by the loop's offset. `XtalLoopEnter` runs before a loop
+// starts and `XtalLoop` at the start of every iteration, a loop that runs
+// past `XtalLimits.maxLoopIterations` is aborted with the line it is on.
+// Without a limit they only mark the loop's line as the one running.
+globalThis.XtalLoops = new Map();
+globalThis.XtalLoopEnter = (line, start) => {
+    globalThis.XtalLastLine = line;
+    if (globalThis.XtalLimits.maxLoopIterations !== Infinity) {
+        globalThis.XtalLoops.set(start, 0);
+    }
+};
+globalThis.XtalLoop = (line, start) => {
+    globalThis.XtalLastLine = line;
+    const { maxLoopIterations } = globalThis.XtalLimits;
+    if (maxLoopIterations === Infinity) {
+        return;
+    }
+    const count = (globalThis.XtalLoops.get(start) ?? 0) + 1;
+    globalThis.XtalLoops.set(start, count);
+    if (count > maxLoopIterations) {
+        throw new RangeError(`Loop at line ${line} ran more than ${maxLoopIterations} iterations, it may never end`);
+    }
+};
+
+// Coverage mode, see `AstReplacerOptions::coverage`. Every statement and
+// branch counts its runs under the id it was registered with.
+globalThis.XtalCoverage = [];
+globalThis.XtalCov = (id) => {
+    globalThis.XtalCoverage[id] = (globalThis.XtalCoverage[id] ?? 0) + 1;
+};
+
+// Inline tests. `test` runs its body right away and records whether it
+// passed. A failing `expect` throws, so the rest of the test is skipped, and
+// outside of a test it is an ordinary runtime error. Async bodies settle
+// before the results are read.
+globalThis.XtalTests = [];
+
+class XtalAssertionError extends Error {
+    constructor(start, message) {
+        super(message);
+        this.name = 'AssertionError';
+        this.start = start;
+    }
+}
+
+const xtalShow = (value) => {
+    switch (typeof value) {
+        case 'string':
+            return JSON.stringify(value);
+        case 'bigint':
+            return `${value}n`;
+        case 'function':
+            return `[Function: ${value.name || '(anonymous)'}]`;
+        case 'symbol':
+            return String(value);
+    }
+    try {
+        return JSON.stringify(value) ?? String(value);
+    } catch {
+        return String(value);
+    }
+};
+
+const xtalEqual = (a, b) => {
+    if (Object.is(a, b)) {
+        return true;
+    }
+    if (typeof a !== 'object' || typeof b !== 'object' || a === null || b === null) {
+        return false;
+    }
+    if (Object.getPrototypeOf(a) !== Object.getPrototypeOf(b)) {
+        return false;
+    }
+    if (a instanceof Date) {
+        return a.getTime() === b.getTime();
+    }
+    if (a instanceof Map || a instanceof Set) {
+        return a.size === b.size && xtalEqual([...a], [...b]);
+    }
+    const keys = Object.keys(a);
+    return keys.length === Object.keys(b).length
+        && keys.every((key) => Object.hasOwn(b, key) && xtalEqual(a[key], b[key]));
+};
+
+globalThis.XtalExpect = (line, start, end, actual) => {
+    const matchers = (negated) => {
+        const check = (pass, description) => {
+            if (pass === negated) {
+                const expectation = `${negated ? 'not ' : ''}${description}`;
+                throw new XtalAssertionError(start, `Expected ${xtalShow(actual)} ${expectation}`);
+            }
+        };
+        return {
+            toBe: (expected) => check(Object.is(actual, expected), `to be ${xtalShow(expected)}`),
+            toEqual: (expected) => check(xtalEqual(actual, expected), `to equal ${xtalShow(expected)}`),
+            toBeTruthy: () => check(Boolean(actual), 'to be truthy'),
+            toBeFalsy: () => check(!actual, 'to be falsy'),
+            toContain: (item) => check(actual?.includes?.(item) ?? false, `to contain ${xtalShow(item)}`),
+            toThrow: () => {
+                let threw = false;
+                try {
+                    actual();
+                } catch {
+                    threw = true;
+                }
+                check(threw, 'to throw');
+            },
+        };
+    };
+    return { ...matchers(false), not: matchers(true) };
+};
+
+globalThis.XtalTest = (line, start, end, name, body) => {
+    const record = { name: String(name), line, start, end, passed: true, failure: null };
+    globalThis.XtalTests.push(record);
+
+    const fail = (error) => {
+        record.passed = false;
+        record.failure = error instanceof XtalAssertionError
+            ? { start: error.start, message: error.message }
+            : { start, message: error instanceof Error ? `${error.name}: ${error.message}` : String(error) };
+    };
+
+    try {
+        const returned = typeof body === 'function' ? body() : undefined;
+        if (returned !== null && typeof returned?.then === 'function') {
+            Promise.resolve(returned).catch(fail);
+        }
+    } catch (error) {
+        fail(error);
+    }
+};
+
+// `bench(fn, { iterations, samples, warmup })` calls `fn` `warmup` times,
+// then `iterations` times split into `samples` batches that are timed one by
+// one. The summary lands on the bench line, the statistics are returned.
+const xtalFormatOps = (ops) =>
+    ops >= 100 ? Math.round(ops).toLocaleString('en-US') : ops.toPrecision(3);
+
+globalThis.XtalBench = (line, start, end, fn, options = {}) => {
+    if (typeof fn !== 'function') {
+        throw new TypeError('bench expects a function');
+    }
+    const iterations = Math.max(1, Math.floor(options.iterations ?? 1000));
+    const samples = Math.min(iterations, Math.max(1, Math.floor(options.samples ?? 10)));
+    const warmup = Math.max(0, Math.floor(options.warmup ?? Math.ceil(iterations / 10)));
+    const batch = Math.floor(iterations / samples);
+
+    // Per call milliseconds of every batch.
+    const times = [];
+    globalThis.XtalLastLine = line;
+    xtalMuted += 1;
+    try {
+        for (let index = 0; index < warmup; index += 1) {
+            fn();
+        }
+        for (let sample = 0; sample < samples; sample += 1) {
+            // The last batch picks up what doesn't divide evenly.
+            const calls = sample === samples - 1 ? iterations - batch * (samples - 1) : batch;
+            const started = XtalNow();
+            for (let index = 0; index < calls; index += 1) {
+                fn();
+            }
+            times.push((XtalNow() - started) / calls);
+        }
+    } finally {
+        xtalMuted -= 1;
+    }
+
+    const mean = times.reduce((sum, time) => sum + time, 0) / times.length;
+    const variance = times.reduce((sum, time) => sum + (time - mean) ** 2, 0) / Math.max(1, times.length - 1);
+    const deviation = Math.sqrt(variance);
+    const stats = {
+        name: fn.name || '(anonymous)',
+        iterations,
+        samples,
+        mean,
+        variance,
+        deviation,
+        opsPerSec: mean > 0 ? 1000 / mean : Infinity,
+        relativeMargin: mean > 0 ? (deviation / mean) * 100 : 0,
+    };
+
+    const summary = `${stats.name}: ${xtalFormatOps(stats.opsPerSec)} ops/sec ±${stats.relativeMargin.toFixed(2)}% (${iterations} iterations)`;
+    globalThis.Xtal(line, start, end, summary);
+    return stats;
+};
+
+// Session runs share one runtime, every run starts with fresh results and hit
+// counts while the snippet's own globals are kept.
+globalThis.XtalReset = () => {
+    globalThis.XtalSites = new Map();
+    xtalKept = 0;
+    globalThis.XtalHits = new Map();
+    globalThis.XtalDropped = 0;
+    globalThis.XtalLastLine = null;
+    globalThis.XtalLoops = new Map();
+    globalThis.XtalTests = [];
+    globalThis.XtalCoverage = [];
+    // A terminated run never leaves its benchmark.
+    xtalMuted = 0;
+};
+
+// Classic JSX factory for .jsx/.tsx tabs, elements evaluate to plain objects.
+globalThis.XtalJsx = (type, props, ...children) => ({
+    type: typeof type === 'function' ? type.name : type,
+    props: { ...props, children },
+});
+globalThis.XtalJsx.Fragment = 'Fragment';
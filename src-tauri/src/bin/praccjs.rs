@@ -0,0 +1,158 @@
+//! Runs a snippet from a file or stdin the way the editor does and prints the
+//! result column, or the whole evaluation as JSON.
+//!
+//! Exits with 1 when the snippet has errors and with 2 when it couldn't be run.
+
+use std::io::{self, Read};
+use std::path::Path;
+use std::process::ExitCode;
+
+use pracc_js_lib::evaluator::{evaluate, RunOptions};
+use pracc_js_lib::tab::Language;
+
+const USAGE: &str = "\
+Usage: praccjs [OPTIONS] [FILE]
+
+Runs FILE, or stdin when it is missing or `-`, and prints its result column.
+
+Options:
+  --json                 Print results and diagnostics as JSON
+  --language <EXT>       js, ts, jsx or tsx, defaults to FILE's extension
+  --timeout <MS>         Stop the run after MS milliseconds
+  --timing               Show how long every expression took
+  --coverage             Add statement and branch coverage to the JSON output
+  -h, --help             Print this help";
+
+struct Args {
+    json: bool,
+    language: Option<Language>,
+    timeout_ms: Option<u64>,
+    timing: bool,
+    coverage: bool,
+    help: bool,
+    file: Option<String>,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args {
+        json: false,
+        language: None,
+        timeout_ms: None,
+        timing: false,
+        coverage: false,
+        help: false,
+        file: None,
+    };
+
+    let mut iter = std::env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--json" => args.json = true,
+            "--language" => {
+                let value = iter.next().ok_or("--language needs a value")?;
+                let language = Language::from_extension(&value)
+                    .ok_or_else(|| format!("Unknown language `{}`", value))?;
+                args.language = Some(language);
+            }
+            "--timeout" => {
+                let value = iter.next().ok_or("--timeout needs a value")?;
+                let timeout_ms = value
+                    .parse()
+                    .map_err(|_| format!("Invalid timeout `{}`", value))?;
+                args.timeout_ms = Some(timeout_ms);
+            }
+            "--timing" => args.timing = true,
+            "--coverage" => args.coverage = true,
+            "-h" | "--help" => args.help = true,
+            "-" => args.file = None,
+            _ if arg.starts_with('-') => return Err(format!("Unknown option `{}`\n\n{}", arg, USAGE)),
+            _ => args.file = Some(arg),
+        }
+    }
+
+    Ok(args)
+}
+
+fn read_source(file: Option<&str>) -> io::Result<String> {
+    match file {
+        Some(file) => std::fs::read_to_string(file),
+        None => {
+            let mut source_text = String::new();
+            io::stdin().read_to_string(&mut source_text)?;
+            Ok(source_text)
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(message) => {
+            eprintln!("{}", message);
+            return ExitCode::from(2);
+        }
+    };
+    if args.help {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+
+    let source_text = match read_source(args.file.as_deref()) {
+        Ok(source_text) => source_text,
+        Err(err) => {
+            eprintln!("Failed to read {}: {}", args.file.as_deref().unwrap_or("stdin"), err);
+            return ExitCode::from(2);
+        }
+    };
+
+    let language = args
+        .language
+        .or_else(|| {
+            let extension = Path::new(args.file.as_deref()?).extension()?;
+            Language::from_extension(extension.to_str()?)
+        })
+        .unwrap_or_default();
+    if !language.is_executable() {
+        eprintln!("JSON files are not executed");
+        return ExitCode::from(2);
+    }
+
+    let mut options = RunOptions {
+        language,
+        timing: args.timing,
+        coverage: args.coverage,
+        ..Default::default()
+    };
+    if let Some(timeout_ms) = args.timeout_ms {
+        options.limits.timeout_ms = timeout_ms;
+    }
+
+    let evaluation = match evaluate(&source_text, options) {
+        Ok(evaluation) => evaluation,
+        Err(err) => {
+            eprintln!("{}", err);
+            return ExitCode::from(2);
+        }
+    };
+
+    if args.json {
+        match serde_json::to_string_pretty(&evaluation) {
+            Ok(json) => println!("{}", json),
+            Err(err) => {
+                eprintln!("{}", err);
+                return ExitCode::from(2);
+            }
+        }
+    } else {
+        print!("{}", evaluation.result_column());
+        for error in &evaluation.errors {
+            eprintln!("{}", error);
+        }
+    }
+
+    if evaluation.has_errors() {
+        ExitCode::from(1)
+    } else {
+        ExitCode::SUCCESS
+    }
+}
@@ -0,0 +1,277 @@
+use std::fmt;
+use std::time::Duration;
+
+use oxc_diagnostics::OxcDiagnostic;
+use oxc_sourcemap::SourceMap;
+use rustyscript::Error;
+use serde::{Deserialize, Serialize};
+
+use crate::ast_replacer::line_index::LineIndex;
+use crate::ast_replacer::utils::TestResult;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+    Advice,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiagnosticKind {
+    Parse,
+    Transform,
+    Runtime,
+    Test,
+}
+
+/// Location in the user's source, lines and columns are 1-indexed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiagnosticSpan {
+    pub offset: usize,
+    pub length: usize,
+    pub line: usize,
+    pub column: usize,
+    // Where the span ends, columns in UTF-16 code units like the editor's.
+    // Offsets are bytes and can't be handed to the editor. Diagnostics stored
+    // before these existed have 0.
+    #[serde(default)]
+    pub end_line: usize,
+    #[serde(default)]
+    pub end_column: usize,
+}
+
+impl DiagnosticSpan {
+    pub fn from_offset(line_index: &LineIndex, offset: usize, length: usize) -> Self {
+        Self {
+            offset,
+            length,
+            line: line_index.line(offset),
+            column: line_index.column(offset),
+            end_line: line_index.line(offset + length),
+            end_column: line_index.column(offset + length),
+        }
+    }
+
+    pub fn from_line_column(line_index: &LineIndex, line: usize, column: usize) -> Self {
+        Self {
+            offset: line_index.offset(line, column),
+            length: 0,
+            line,
+            column,
+            end_line: line,
+            end_column: column,
+        }
+    }
+}
+
+/// Secondary location of a diagnostic, e.g. where an unclosed bracket opened.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DiagnosticLabel {
+    pub span: DiagnosticSpan,
+    pub message: Option<String>,
+}
+
+/// A problem with the snippet, reported to the frontend so the editor can
+/// mark the line it belongs to.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Diagnostic {
+    pub kind: DiagnosticKind,
+    pub severity: Severity,
+    pub message: String,
+    // Primary location, the first label oxc marks as primary.
+    pub span: Option<DiagnosticSpan>,
+    #[serde(default)]
+    pub labels: Vec<DiagnosticLabel>,
+    #[serde(default)]
+    pub help: Option<String>,
+    // Call stack of runtime errors, innermost frame first.
+    #[serde(default)]
+    pub stack: Vec<StackFrame>,
+}
+
+/// A frame of a runtime error's stack trace, located in the user's source.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StackFrame {
+    pub function: Option<String>,
+    pub span: DiagnosticSpan,
+}
+
+impl Diagnostic {
+    /// Converts a parser or transformer diagnostic from oxc, its spans are
+    /// offsets into the user's source.
+    pub fn from_oxc(error: &OxcDiagnostic, kind: DiagnosticKind, line_index: &LineIndex) -> Self {
+        let labels = error
+            .labels
+            .iter()
+            .flatten()
+            .map(|label| DiagnosticLabel {
+                span: DiagnosticSpan::from_offset(line_index, label.offset(), label.len()),
+                message: label.label().map(str::to_string),
+            })
+            .collect::<Vec<_>>();
+
+        let span = error
+            .labels
+            .iter()
+            .flatten()
+            .find(|label| label.primary())
+            .or_else(|| error.labels.iter().flatten().next())
+            .map(|label| DiagnosticSpan::from_offset(line_index, label.offset(), label.len()));
+
+        let severity = match error.severity {
+            oxc_diagnostics::Severity::Error => Severity::Error,
+            oxc_diagnostics::Severity::Warning => Severity::Warning,
+            oxc_diagnostics::Severity::Advice => Severity::Advice,
+        };
+
+        Self {
+            kind,
+            severity,
+            message: error.message.to_string(),
+            span,
+            labels,
+            help: error.help.as_ref().map(|help| help.to_string()),
+            stack: Vec::new(),
+        }
+    }
+
+    /// Maps an exception thrown by the instrumented module back to the
+    /// position in the user's source through the codegen source map.
+    pub fn from_runtime_error(
+        error: &Error,
+        source_map: Option<&SourceMap>,
+        line_index: &LineIndex,
+        module_name: &str,
+    ) -> Self {
+        let Error::JsError(js_error) = error else {
+            return Self {
+                kind: DiagnosticKind::Runtime,
+                severity: Severity::Error,
+                message: error.to_string(),
+                span: None,
+                labels: Vec::new(),
+                help: None,
+                stack: Vec::new(),
+            };
+        };
+
+        let lookup_table = source_map.map(SourceMap::generate_lookup_table);
+        // Frames of the Xtal helper and the runtime itself can't be mapped,
+        // only the ones that belong to the snippet are kept.
+        let stack = js_error
+            .frames
+            .iter()
+            .filter(|frame| {
+                frame
+                    .file_name
+                    .as_deref()
+                    .is_some_and(|name| name.ends_with(module_name))
+            })
+            .filter_map(|frame| {
+                let (source_map, lookup_table) = (source_map?, lookup_table.as_ref()?);
+                let token = source_map.lookup_token(
+                    lookup_table,
+                    frame.line_number?.saturating_sub(1) as u32,
+                    frame.column_number?.saturating_sub(1) as u32,
+                )?;
+                Some(StackFrame {
+                    function: frame.function_name.clone(),
+                    span: DiagnosticSpan::from_line_column(
+                        line_index,
+                        token.get_src_line() as usize + 1,
+                        token.get_src_col() as usize + 1,
+                    ),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        Self {
+            kind: DiagnosticKind::Runtime,
+            severity: Severity::Error,
+            message: js_error.exception_message.clone(),
+            // The innermost frame is where the exception was thrown.
+            span: stack.first().map(|frame| frame.span),
+            labels: Vec::new(),
+            help: None,
+            stack,
+        }
+    }
+
+    /// A run that was terminated after `timeout`, at the last line that ran a
+    /// loop iteration or reported a value if there is one.
+    pub fn timeout(timeout: Duration, line: Option<usize>, line_index: &LineIndex) -> Self {
+        Self {
+            kind: DiagnosticKind::Runtime,
+            severity: Severity::Error,
+            message: format!("Timed out after {}s", timeout.as_secs_f64()),
+            span: line.map(|line| DiagnosticSpan::from_line_column(line_index, line, 1)),
+            labels: Vec::new(),
+            help: Some("Raise the timeout in the tab's limits if the snippet needs longer".to_string()),
+            stack: Vec::new(),
+        }
+    }
+
+    /// A run that was terminated for using more than `max_heap_mb` of heap.
+    pub fn heap_exhausted(max_heap_mb: usize) -> Self {
+        Self {
+            kind: DiagnosticKind::Runtime,
+            severity: Severity::Error,
+            message: format!("Heap limit of {} MB exceeded", max_heap_mb),
+            span: None,
+            labels: Vec::new(),
+            help: Some("Raise the heap size in the tab's limits if the snippet needs more memory".to_string()),
+            stack: Vec::new(),
+        }
+    }
+
+    /// A failed inline test, located at the assertion that failed.
+    pub fn from_test(test: &TestResult, line_index: &LineIndex) -> Option<Self> {
+        let failure = test.failure.as_ref()?;
+        Some(Self {
+            kind: DiagnosticKind::Test,
+            severity: Severity::Error,
+            message: format!("{}: {}", test.name, failure.message),
+            span: Some(DiagnosticSpan::from_offset(line_index, failure.start, 0)),
+            labels: Vec::new(),
+            help: None,
+            stack: Vec::new(),
+        })
+    }
+
+    /// Values reported beyond the result limit of a run.
+    pub fn results_dropped(dropped: usize, max_results: usize) -> Self {
+        Self {
+            kind: DiagnosticKind::Runtime,
+            severity: Severity::Warning,
+            message: format!("{} results dropped, the limit is {}", dropped, max_results),
+            span: None,
+            labels: Vec::new(),
+            help: None,
+            stack: Vec::new(),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        if self.stack.is_empty() {
+            if let Some(span) = &self.span {
+                write!(f, "\n    at line {}:{}", span.line, span.column)?;
+            }
+        }
+        for frame in &self.stack {
+            match &frame.function {
+                Some(function) => write!(
+                    f,
+                    "\n    at {} (line {}:{})",
+                    function, frame.span.line, frame.span.column
+                )?,
+                None => write!(f, "\n    at line {}:{}", frame.span.line, frame.span.column)?,
+            }
+        }
+        Ok(())
+    }
+}
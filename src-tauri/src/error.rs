@@ -0,0 +1,70 @@
+use std::fmt;
+use std::time::Duration;
+
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+
+/// Failures of the evaluation commands themselves, as opposed to problems
+/// with the snippet, which are reported as diagnostics.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    // The tabs could not be read from or written to the store.
+    Store(String),
+    // A runtime could not be created, or its helpers not installed.
+    RuntimeInit(String),
+    // The worker thread went away without sending a result.
+    WorkerPanicked,
+    // The worker didn't stop after its run was terminated.
+    Timeout(Duration),
+}
+
+impl Error {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::Store(_) => "store",
+            Error::RuntimeInit(_) => "runtime_init",
+            Error::WorkerPanicked => "worker_panicked",
+            Error::Timeout(_) => "timeout",
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Store(message) => write!(f, "Tab store unavailable: {}", message),
+            Error::RuntimeInit(message) => write!(f, "Runtime initialization failed: {}", message),
+            Error::WorkerPanicked => write!(f, "Evaluation worker panicked"),
+            Error::Timeout(timeout) => write!(
+                f,
+                "Evaluation worker did not stop after {}s",
+                timeout.as_secs_f64()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+// Commands reject with `{ kind, message }`, so the frontend can tell the
+// failures apart and show the message in the tab.
+impl Serialize for Error {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("Error", 2)?;
+        state.serialize_field("kind", self.kind())?;
+        state.serialize_field("message", &self.to_string())?;
+        state.end()
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Error::Store(err.to_string())
+    }
+}
+
+impl From<tauri_plugin_svelte::Error> for Error {
+    fn from(err: tauri_plugin_svelte::Error) -> Self {
+        Error::Store(err.to_string())
+    }
+}
@@ -0,0 +1,528 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use oxc_allocator::Allocator;
+use oxc_codegen::{CodeGenerator, CodegenOptions};
+use oxc_parser::Parser;
+use oxc_semantic::SemanticBuilder;
+use oxc_sourcemap::SourceMap;
+use oxc_transformer::{JsxOptions, JsxRuntime, TransformOptions, Transformer};
+use rustyscript::deno_core::v8::IsolateHandle;
+use rustyscript::deno_core::PollEventLoopOptions;
+use rustyscript::{Module, Runtime, RuntimeOptions};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::ast_replacer::inspect::InspectOptions;
+use crate::ast_replacer::lib::{AstReplacer, AstReplacerOptions, CoveragePoint, Site};
+use crate::ast_replacer::line_index::LineIndex;
+use crate::ast_replacer::utils::{
+    collect_coverage, collect_line_hits, collect_results, collect_tests, transform_to_result,
+    CoverageRange, ResultEntry, TestResult, LAST_VALUES,
+};
+use crate::diagnostic::{Diagnostic, DiagnosticKind, Severity};
+use crate::error::Error;
+use crate::limits::Limits;
+use crate::tab::Language;
+
+pub const SNIPPET_MODULE: &str = "snippet.js";
+pub const SESSION_SCRIPT: &str = "session.js";
+// How long a terminated worker gets to report what it has.
+const TERMINATION_GRACE: Duration = Duration::from_secs(1);
+const XTAL_HELPER: &str = include_str!("ast_replacer/xtal.js");
+
+/// How a snippet is run, taken from its tab.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RunOptions {
+    pub language: Language,
+    pub inspect: InspectOptions,
+    pub limits: Limits,
+    // Measure how long every instrumented expression takes.
+    pub timing: bool,
+    // Count which statements and branches run.
+    pub coverage: bool,
+}
+
+impl RunOptions {
+    fn replacer_options(&self, session: bool) -> AstReplacerOptions {
+        AstReplacerOptions {
+            session,
+            timing: self.timing,
+            coverage: self.coverage,
+        }
+    }
+}
+
+/// Everything a single run of a snippet produced.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Evaluation {
+    pub results: Vec<ResultEntry>,
+    pub diagnostics: Vec<Diagnostic>,
+    // Rendered messages for the plain text error view.
+    pub errors: Vec<String>,
+    pub tests: Vec<TestResult>,
+    // Statements and branches with how often they ran, in coverage mode.
+    pub coverage: Vec<CoverageRange>,
+}
+
+impl Evaluation {
+    /// The result column the editor shows next to the snippet, one line per
+    /// source line.
+    pub fn result_column(&self) -> String {
+        transform_to_result(&collect_line_hits(&self.results), &self.diagnostics)
+    }
+
+    /// Whether the snippet failed to parse, transform or run.
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics
+            .iter()
+            .any(|diagnostic| diagnostic.severity == Severity::Error)
+    }
+
+    /// Moves every reported position down by `lines` lines and `offset`
+    /// bytes, used when a selection is evaluated on its own. The errors are
+    /// rendered again from the moved diagnostics.
+    pub fn shift(&mut self, lines: usize, offset: usize) {
+        for entry in &mut self.results {
+            entry.line += lines;
+            entry.end_line += lines;
+            entry.start += offset;
+            entry.end += offset;
+        }
+
+        for range in &mut self.coverage {
+            range.line += lines;
+            range.end_line += lines;
+            range.start += offset;
+            range.end += offset;
+        }
+
+        for test in &mut self.tests {
+            test.line += lines;
+            test.start += offset;
+            test.end += offset;
+            if let Some(failure) = &mut test.failure {
+                failure.line += lines;
+                failure.start += offset;
+            }
+        }
+
+        for diagnostic in &mut self.diagnostics {
+            let spans = diagnostic
+                .span
+                .iter_mut()
+                .chain(diagnostic.labels.iter_mut().map(|label| &mut label.span))
+                .chain(diagnostic.stack.iter_mut().map(|frame| &mut frame.span));
+            for span in spans {
+                span.line += lines;
+                span.end_line += lines;
+                span.offset += offset;
+            }
+        }
+
+        // The rendered errors carry the positions as well.
+        self.errors = self.diagnostics.iter().map(Diagnostic::to_string).collect();
+    }
+}
+
+/// Generated program of a snippet, ready to run.
+struct Instrumented {
+    code: String,
+    source_map: Option<SourceMap>,
+    sites: Vec<Site>,
+    coverage: Vec<CoveragePoint>,
+}
+
+/// Parses, instruments and transforms a snippet. Parse and transform errors
+/// are added to `evaluation`, nothing is returned when there are any.
+fn instrument(
+    line_index: &LineIndex,
+    language: Language,
+    options: AstReplacerOptions,
+    file_name: &str,
+    evaluation: &mut Evaluation,
+) -> Option<Instrumented> {
+    let source_text = line_index.source_text();
+    let allocator = Allocator::default();
+    let source_type = if options.session {
+        language.source_type().with_module(false)
+    } else {
+        language.source_type()
+    };
+    let ret = Parser::new(&allocator, source_text, source_type).parse();
+
+    for error in ret.errors {
+        evaluation.diagnostics.push(Diagnostic::from_oxc(
+            &error,
+            DiagnosticKind::Parse,
+            line_index,
+        ));
+        let error = error.with_source_code(source_text.to_string());
+        evaluation.errors.push(format!("{:?}", error));
+    }
+
+    if !evaluation.errors.is_empty() {
+        return None;
+    }
+
+    let program = allocator.alloc(ret.program);
+    // Instrument the typed AST, the line numbers embedded into the Xtal
+    // calls refer to the user's original source.
+    let mut replacer = AstReplacer::new(&allocator, line_index, options);
+    replacer.build(program);
+    let (sites, coverage) = replacer.into_parts();
+
+    // Strip types and compile JSX after instrumentation.
+    if language.needs_transform() {
+        let (symbols, scopes) = SemanticBuilder::new()
+            .build(program)
+            .semantic
+            .into_symbol_table_and_scope_tree();
+        let transformed = Transformer::new(
+            &allocator,
+            Path::new(language.file_name()),
+            &transform_options(),
+        )
+        .build_with_symbols_and_scopes(symbols, scopes, program);
+
+        for error in transformed.errors {
+            evaluation.diagnostics.push(Diagnostic::from_oxc(
+                &error,
+                DiagnosticKind::Transform,
+                line_index,
+            ));
+            let error = error.with_source_code(source_text.to_string());
+            evaluation.errors.push(format!("{:?}", error));
+        }
+    }
+
+    if !evaluation.errors.is_empty() {
+        return None;
+    }
+
+    // The source map is what runtime errors are mapped back with.
+    let generated = CodeGenerator::new()
+        .with_options(CodegenOptions {
+            source_map_path: Some(PathBuf::from(file_name)),
+            ..Default::default()
+        })
+        .build(program);
+
+    Some(Instrumented {
+        code: generated.code,
+        source_map: generated.map,
+        sites,
+        coverage,
+    })
+}
+
+/// Transform options for TypeScript and JSX tabs. JSX is compiled against the
+/// classic runtime with the `XtalJsx` factory installed by the Xtal helper,
+/// since there is no React to import from inside the runtime.
+fn transform_options() -> TransformOptions {
+    TransformOptions {
+        jsx: JsxOptions {
+            runtime: JsxRuntime::Classic,
+            pragma: Some("XtalJsx".to_string()),
+            pragma_frag: Some("XtalJsx.Fragment".to_string()),
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+/// Creates a runtime for snippets, with the heap limited to `limits`.
+pub fn create_runtime(limits: Limits) -> Result<Runtime, Error> {
+    Runtime::new(RuntimeOptions {
+        timeout: limits.timeout(),
+        max_heap_size: Some(limits.max_heap_size()),
+        ..Default::default()
+    })
+    .map_err(runtime_init)
+}
+
+/// Installs the helpers the instrumented code reports its values through,
+/// along with the clock timed expressions and benchmarks read.
+pub fn install_helpers(
+    runtime: &mut Runtime,
+    inspect_options: InspectOptions,
+    limits: Limits,
+) -> Result<(), Error> {
+    // Milliseconds since the runtime was created, with sub-microsecond
+    // resolution unlike the coarsened `performance.now()`.
+    let origin = Instant::now();
+    runtime
+        .register_function("xtal_now", move |_: &[Value]| {
+            Ok(Value::from(origin.elapsed().as_secs_f64() * 1000.0))
+        })
+        .map_err(runtime_init)?;
+    runtime.eval::<()>(XTAL_HELPER).map_err(runtime_init)?;
+    runtime
+        .eval::<()>(format!("globalThis.XtalLastValues = {};", LAST_VALUES))
+        .map_err(runtime_init)?;
+    runtime.eval::<()>(inspect_options.to_script()).map_err(runtime_init)?;
+    runtime.eval::<()>(limits.to_script()).map_err(runtime_init)
+}
+
+fn runtime_init(err: rustyscript::Error) -> Error {
+    Error::RuntimeInit(err.to_string())
+}
+
+/// Runs a snippet once as an ES module, so top-level await works. The
+/// runtime is expected to have the helpers installed. `timed_out` is set by
+/// whoever terminates the run for taking too long.
+pub fn evaluate_module(
+    runtime: &mut Runtime,
+    source_text: &str,
+    options: RunOptions,
+    timed_out: &AtomicBool,
+) -> Evaluation {
+    let started = Instant::now();
+    let mut evaluation = Evaluation::default();
+    // Built once and shared by every line/column lookup of this snippet.
+    let line_index = LineIndex::new(source_text);
+    let Some(instrumented) = instrument(
+        &line_index,
+        options.language,
+        options.replacer_options(false),
+        SNIPPET_MODULE,
+        &mut evaluation,
+    ) else {
+        return evaluation;
+    };
+
+    // Runtime errors come from this single run, the values reported before
+    // the exception are still collected below.
+    let module = Module::new(SNIPPET_MODULE, &instrumented.code);
+    let loaded = runtime.load_module(&module).map(|_| ());
+
+    let run = Run {
+        instrumented: &instrumented,
+        line_index: &line_index,
+        file_name: SNIPPET_MODULE,
+        limits: options.limits,
+        started,
+        timed_out,
+    };
+    finish(runtime, loaded, run, &mut evaluation);
+    evaluation
+}
+
+/// Runs a snippet as a script in a runtime that is kept between runs, the
+/// globals it declares stay around for the next one.
+pub fn evaluate_script(
+    runtime: &mut Runtime,
+    source_text: &str,
+    options: RunOptions,
+    timed_out: &AtomicBool,
+) -> Result<Evaluation, Error> {
+    let started = Instant::now();
+    // A previous run that timed out leaves the isolate terminating.
+    runtime.deno_runtime().v8_isolate().cancel_terminate_execution();
+    runtime.eval::<()>("XtalReset()").map_err(runtime_init)?;
+    runtime.eval::<()>(options.inspect.to_script()).map_err(runtime_init)?;
+    runtime.eval::<()>(options.limits.to_script()).map_err(runtime_init)?;
+
+    let mut evaluation = Evaluation::default();
+    let line_index = LineIndex::new(source_text);
+    let Some(instrumented) = instrument(
+        &line_index,
+        options.language,
+        options.replacer_options(true),
+        SESSION_SCRIPT,
+        &mut evaluation,
+    ) else {
+        return Ok(evaluation);
+    };
+
+    let executed = runtime
+        .deno_runtime()
+        .execute_script(SESSION_SCRIPT, instrumented.code.clone())
+        .map(|_| ())
+        .map_err(rustyscript::Error::from);
+
+    let run = Run {
+        instrumented: &instrumented,
+        line_index: &line_index,
+        file_name: SESSION_SCRIPT,
+        limits: options.limits,
+        started,
+        timed_out,
+    };
+    finish(runtime, executed, run, &mut evaluation);
+    Ok(evaluation)
+}
+
+/// A snippet being run, with what is needed to report on it.
+struct Run<'a> {
+    instrumented: &'a Instrumented,
+    line_index: &'a LineIndex,
+    file_name: &'a str,
+    limits: Limits,
+    started: Instant,
+    // Set before the isolate is terminated for running past the timeout.
+    timed_out: &'a AtomicBool,
+}
+
+/// Drains the event loop, maps runtime errors and collects the results.
+fn finish(
+    runtime: &mut Runtime,
+    executed: Result<(), rustyscript::Error>,
+    run: Run,
+    evaluation: &mut Evaluation,
+) {
+    // Drain pending timers and promises so awaited values are reported
+    // before the results are read. Loading and draining share the timeout.
+    let remaining = run.limits.timeout().saturating_sub(run.started.elapsed());
+    let drained = runtime.block_on_event_loop(PollEventLoopOptions::default(), Some(remaining));
+
+    // A terminated isolate runs nothing, not even the evals reading the
+    // results below.
+    runtime.deno_runtime().v8_isolate().cancel_terminate_execution();
+
+    if runtime.heap_exhausted_token().is_cancelled() {
+        // V8 terminated the isolate when it neared the heap limit, whatever
+        // error the run ended with is a consequence of that.
+        let diagnostic = Diagnostic::heap_exhausted(run.limits.max_heap_mb);
+        evaluation.errors.push(diagnostic.to_string());
+        evaluation.diagnostics.push(diagnostic);
+    } else if run.timed_out.load(Ordering::SeqCst) || run.started.elapsed() >= run.limits.timeout() {
+        // The isolate was terminated, or the event loop gave up. Values
+        // reported up to then are still read below.
+        let line = runtime
+            .eval::<Option<usize>>("globalThis.XtalLastLine")
+            .ok()
+            .flatten();
+        let diagnostic = Diagnostic::timeout(run.limits.timeout(), line, run.line_index);
+        evaluation.errors.push(diagnostic.to_string());
+        evaluation.diagnostics.push(diagnostic);
+    } else {
+        for err in [executed, drained].into_iter().filter_map(Result::err) {
+            let diagnostic = Diagnostic::from_runtime_error(
+                &err,
+                run.instrumented.source_map.as_ref(),
+                run.line_index,
+                run.file_name,
+            );
+            evaluation.errors.push(diagnostic.to_string());
+            evaluation.diagnostics.push(diagnostic);
+        }
+    }
+
+    let dropped = runtime
+        .eval::<usize>("globalThis.XtalDropped")
+        .unwrap_or_default();
+    if dropped > 0 {
+        let diagnostic = Diagnostic::results_dropped(dropped, run.limits.max_results);
+        evaluation.errors.push(diagnostic.to_string());
+        evaluation.diagnostics.push(diagnostic);
+    }
+
+    let debug_tests: Vec<Value> = runtime
+        .eval("globalThis.XtalTests")
+        .unwrap_or_else(|_| Vec::new());
+    evaluation.tests = collect_tests(debug_tests, run.line_index);
+    for test in &evaluation.tests {
+        if let Some(diagnostic) = Diagnostic::from_test(test, run.line_index) {
+            evaluation.errors.push(diagnostic.to_string());
+            evaluation.diagnostics.push(diagnostic);
+        }
+    }
+
+    let debug_results: Vec<Value> = runtime
+        .eval("XtalCollect()")
+        .unwrap_or_else(|_| Vec::new());
+
+    evaluation.results = collect_results(debug_results, &run.instrumented.sites, run.line_index);
+
+    if !run.instrumented.coverage.is_empty() {
+        let counts: Vec<usize> = runtime
+            .eval("Array.from(globalThis.XtalCoverage, (count) => count ?? 0)")
+            .unwrap_or_default();
+        evaluation.coverage =
+            collect_coverage(&run.instrumented.coverage, &counts, run.line_index);
+    }
+}
+
+/// Parses, instruments and runs a snippet once in a fresh runtime. This is
+/// what the editor does for tabs outside of session mode, without a tab.
+pub fn evaluate(source_text: &str, options: RunOptions) -> Result<Evaluation, Error> {
+    run_isolated(source_text.to_string(), options, |_| {})
+}
+
+/// Evaluates a snippet in a fresh runtime on a dedicated worker thread,
+/// terminating it when it runs longer than the timeout. The isolate is handed
+/// to `on_isolate` as soon as it exists, so the run can be stopped early.
+pub fn run_isolated(
+    source_text: String,
+    options: RunOptions,
+    on_isolate: impl FnOnce(IsolateHandle),
+) -> Result<Evaluation, Error> {
+    let limits = options.limits;
+    let timed_out = Arc::new(AtomicBool::new(false));
+    let worker_timed_out = timed_out.clone();
+    // Create a channel to receive the thread-safe handle.
+    let (tx_handle, rx_handle) = channel();
+    // Create a channel to receive the worker result.
+    let (tx_result, rx_result) = channel();
+
+    // Spawn a dedicated worker thread. Nothing is left to report to when the
+    // main thread has stopped waiting, so failed sends are ignored.
+    thread::spawn(move || {
+        // Create the single runtime the instrumented snippet is evaluated in.
+        let mut runtime = match create_runtime(limits) {
+            Ok(runtime) => runtime,
+            Err(err) => {
+                let _ = tx_handle.send(Err(err));
+                return;
+            }
+        };
+
+        // Immediately send the thread-safe handle so the main thread can cancel if needed.
+        let ts_handle = runtime
+            .deno_runtime()
+            .v8_isolate()
+            .thread_safe_handle();
+        let _ = tx_handle.send(Ok(ts_handle));
+
+        let evaluation = install_helpers(&mut runtime, options.inspect, limits)
+            .map(|()| evaluate_module(&mut runtime, &source_text, options, &worker_timed_out));
+
+        // Send the result back to the main thread.
+        let _ = tx_result.send(evaluation);
+    });
+
+    // In the main thread, receive the thread-safe handle.
+    let ts_handle = rx_handle.recv().map_err(|_| Error::WorkerPanicked)??;
+    on_isolate(ts_handle.clone());
+
+    // Wait for the worker to complete.
+    match rx_result.recv_timeout(limits.timeout()) {
+        Ok(res) => res, // Worker finished quickly.
+        Err(RecvTimeoutError::Timeout) => {
+            // Timeout expired: terminate the execution, the worker still
+            // reports what ran until then.
+            timed_out.store(true, Ordering::SeqCst);
+            ts_handle.terminate_execution();
+            await_terminated(&rx_result, limits)
+        }
+        Err(RecvTimeoutError::Disconnected) => Err(Error::WorkerPanicked),
+    }
+}
+
+/// Waits for the result of a worker whose run was just terminated. A worker
+/// that doesn't respond is given up on rather than joined.
+pub fn await_terminated(
+    rx_result: &Receiver<Result<Evaluation, Error>>,
+    limits: Limits,
+) -> Result<Evaluation, Error> {
+    match rx_result.recv_timeout(TERMINATION_GRACE) {
+        Ok(res) => res,
+        Err(RecvTimeoutError::Timeout) => Err(Error::Timeout(limits.timeout())),
+        Err(RecvTimeoutError::Disconnected) => Err(Error::WorkerPanicked),
+    }
+}
@@ -1,169 +1,247 @@
-mod ast_replacer;
+pub mod ast_replacer;
+pub mod diagnostic;
+pub mod error;
+pub mod evaluator;
+pub mod limits;
 mod plugins;
-mod tab;
+mod runs;
+mod session;
+pub mod tab;
 
-use std::sync::mpsc::channel;
-use std::sync::mpsc::RecvTimeoutError;
-use std::thread;
-use std::time::Duration;
+use std::ops::RangeInclusive;
 
-use ast_replacer::lib::AstReplacer;
-use ast_replacer::utils::transform_to_result;
-use oxc_allocator::Allocator;
-
-use oxc_ast::AstBuilder;
-use oxc_codegen::{CodeGenerator, CodegenOptions};
-use oxc_parser::Parser;
-use oxc_semantic::SemanticBuilder;
-use oxc_span::SourceType;
-
-use rustyscript::deno_core::error::AnyError;
-use rustyscript::RuntimeOptions;
+use ast_replacer::line_index::LineIndex;
+use ast_replacer::utils::{collect_line_hits, transform_to_result, value_to_string};
+use diagnostic::Diagnostic;
+use error::Error;
+use evaluator::{run_isolated, Evaluation, RunOptions};
+use limits::Limits;
+use runs::Runs;
+use session::Sessions;
 use tab::Tab;
 use tauri::Manager;
 
-use rustyscript::Error;
-use rustyscript::Runtime;
 use serde_json::Value;
 use tauri_plugin_svelte::ManagerExt;
 
 const STORE_NAME: &str = "storage2";
 const TABS_KEY: &str = "tabs";
+const SETTINGS_STORE_NAME: &str = "settings";
+const LIMITS_KEY: &str = "limits";
 
 #[tauri::command]
 async fn handle_editor_changes(
     source_text: String,
     tab_id: String,
     app: tauri::AppHandle,
+    sessions: tauri::State<'_, Sessions>,
+    runs: tauri::State<'_, Runs>,
 ) -> Result<(), Error> {
+    // Every edit supersedes the run still in flight for this tab.
+    let revision = runs.start(&tab_id);
 
     // Retrieve current tabs.
-    let mut tabs_data = app
-        .svelte()
-        .try_get::<Vec<Tab>>(STORE_NAME, TABS_KEY)
-        .unwrap();
-
-    // Create a channel to receive the thread-safe handle.
-    let (tx_handle, rx_handle) = channel();
-    // Create a channel to receive the worker result.
-    let (tx_result, rx_result) = channel();
-
-    // Clone the source text for the worker thread.
-    let source_text_worker = source_text.clone();
-
-    // Spawn a dedicated worker thread.
-    let join_handle = thread::spawn(move || -> Result<(String, Vec<String>), Error> {
-        // Create a runtime for evaluating the script.
-        let mut runtime = Runtime::new(RuntimeOptions {
-            timeout: Duration::from_secs(2),
-            ..Default::default()
-        }).unwrap();
-
-        // Immediately send the thread-safe handle so the main thread can cancel if needed.
-        let ts_handle = runtime
-            .deno_runtime()
-            .v8_isolate()
-            .thread_safe_handle();
-        tx_handle.send(ts_handle).expect("Failed to send thread-safe handle");
-
-        // Evaluate the main source text.
-        let mut error_messages = Vec::new();
-        if let Err(err) = runtime.eval::<()>(source_text_worker.clone()) {
-            error_messages.push(err.as_highlighted(Default::default()));
-        }
+    let tabs_data = app.svelte().try_get::<Vec<Tab>>(STORE_NAME, TABS_KEY)?;
 
-        // Perform parsing, semantic analysis, and AST processing.
-        let allocator = Allocator::default();
-        let source_type = SourceType::default();
-        let ret = Parser::new(&allocator, &source_text_worker, source_type).parse();
+    // The tab's language decides how the snippet is parsed, transformed and run.
+    let (options, session) = tabs_data
+        .iter()
+        .find(|tab| tab.id == tab_id)
+        .map(|tab| (run_options(&app, tab), tab.session))
+        .unwrap_or_else(|| {
+            let options = RunOptions {
+                limits: global_limits(&app),
+                ..Default::default()
+            };
+            (options, false)
+        });
 
-        for error in ret.errors {
-            let error = error.with_source_code(source_text_worker.clone());
-            error_messages.push(format!("{:?}", error));
-        }
-        let _semantic_ret = SemanticBuilder::new().build(&ret.program);
-        let _ast_builder = AstBuilder::new(&allocator);
-
-        // If no errors so far, transform the code.
-        let transformed_code = if error_messages.is_empty() {
-            let program = allocator.alloc(ret.program);
-            let _ast_pass = AstReplacer::new(&allocator, source_text_worker.clone()).build(program);
-            let new_code = CodeGenerator::new()
-                .with_options(CodegenOptions::default())
-                .build(&program)
-                .code;
-
-            // Create a second runtime to evaluate a helper module.
-            let mut runtime2 = Runtime::new(Default::default()).unwrap();
-            runtime2
-                .eval::<()>(
-                    r#"
-                    // Xtal is a function that wraps call expressions
-                    globalThis.Xtal = async (line, ...valuePromise) => {
-                        if (typeof globalThis.XtalResults === 'undefined') {
-                            globalThis.XtalResults = [];
-                        }
-                        const resolvedValues = await Promise.all(
-                            valuePromise.map(async (value) => await value)
-                        );
-                        resolvedValues.forEach(value => {
-                            globalThis.XtalResults.push({ line, value });
-                        });
-                    };
-                    "#,
-                )
-                .unwrap();
-
-            if let Err(err) = runtime2.eval::<()>(new_code) {
-                error_messages.push(err.as_highlighted(Default::default()));
-            }
-
-            let debug_results: Vec<Value> = runtime2
-                .eval("globalThis.XtalResults")
-                .unwrap_or_else(|_| Vec::new());
-
-            transform_to_result(debug_results)
-        } else {
-            String::new()
-        };
-
-        let result = (transformed_code, error_messages);
-        // Send the result back to the main thread.
-        tx_result.send(result.clone()).expect("Failed to send result");
-        Ok(result)
-    });
-
-    // In the main thread, receive the thread-safe handle.
-    let ts_handle = rx_handle.recv().expect("Failed to receive thread-safe handle");
-
-    // Wait up to 2 seconds for the worker to complete.
-    let worker_result = rx_result.recv_timeout(Duration::from_secs(2));
-    let (transformed_result, error_messages) = match worker_result {
-        Ok(res) => res, // Worker finished quickly.
-        Err(RecvTimeoutError::Timeout) => {
-            // Timeout expired: terminate the execution.
-            ts_handle.terminate_execution();
-            // Wait for the worker thread to finish.
-            join_handle.join().expect("Worker thread panicked")?
-        }
-        Err(e) => return Err(AnyError::msg(format!("Worker channel error: {:?}", e)).into()),
+    // JSON scratchpads are only validated, there is nothing to execute.
+    if !options.language.is_executable() {
+        let (result, errors) = evaluate_json(&source_text);
+        return runs
+            .finish(&tab_id, revision, || {
+                write_tab(&app, &tab_id, |tab| {
+                    tab.result = result;
+                    tab.errors = errors;
+                    tab.hits = Vec::new();
+                    tab.results = Vec::new();
+                    tab.diagnostics = Vec::new();
+                    tab.tests = Vec::new();
+                    tab.coverage_ranges = Vec::new();
+                })
+            })
+            .unwrap_or(Ok(()));
+    }
+
+    // Session tabs keep their runtime between runs, the others get a fresh
+    // one every time.
+    let on_isolate = |isolate| runs.attach(&tab_id, revision, isolate);
+    let evaluation = if session {
+        sessions.run(&tab_id, source_text, options, on_isolate)
+    } else {
+        run_isolated(source_text, options, on_isolate)
     };
 
-    // Update the appropriate tab.
-    if let Some(tab) = tabs_data.iter_mut().find(|tab| tab.id == tab_id) {
-        tab.result = transformed_result;
-        tab.errors = error_messages.join("\n");
+    // Only the latest revision writes its results, a terminated run that
+    // finishes late must not overwrite them. Its failures aren't reported
+    // either.
+    runs.finish(&tab_id, revision, || {
+        let evaluation = evaluation?;
+        write_tab(&app, &tab_id, |tab| update_tab(tab, evaluation, None))
+    })
+    .unwrap_or(Ok(()))
+}
+
+/// Evaluates the selected lines in the tab's session, starting at 1-based
+/// `start_line` of the tab. Only the results of those lines are replaced.
+#[tauri::command]
+async fn run_selection(
+    source_text: String,
+    start_line: usize,
+    tab_id: String,
+    app: tauri::AppHandle,
+    sessions: tauri::State<'_, Sessions>,
+    runs: tauri::State<'_, Runs>,
+) -> Result<(), Error> {
+    // A selection run is superseded by edits and other runs like any other.
+    let revision = runs.start(&tab_id);
+
+    let tabs_data = app.svelte().try_get::<Vec<Tab>>(STORE_NAME, TABS_KEY)?;
+
+    let Some(tab) = tabs_data.iter().find(|tab| tab.id == tab_id) else {
+        return Ok(());
+    };
+    if !tab.language.is_executable() {
+        return Ok(());
+    }
+
+    let start_line = start_line.max(1);
+    let offset = LineIndex::new(&tab.content).offset(start_line, 1);
+    let lines = start_line..=start_line + LineIndex::new(&source_text).line_count() - 1;
+
+    let on_isolate = |isolate| runs.attach(&tab_id, revision, isolate);
+    let evaluation = sessions.run(&tab_id, source_text, run_options(&app, tab), on_isolate);
+
+    runs.finish(&tab_id, revision, || {
+        let mut evaluation = evaluation?;
+        evaluation.shift(start_line - 1, offset);
+        write_tab(&app, &tab_id, |tab| update_tab(tab, evaluation, Some(lines)))
+    })
+    .unwrap_or(Ok(()))
+}
+
+/// Drops the tab's session runtime, the next run starts from scratch.
+#[tauri::command]
+fn reset_session(tab_id: String, sessions: tauri::State<'_, Sessions>) {
+    sessions.reset(&tab_id);
+}
+
+fn run_options(app: &tauri::AppHandle, tab: &Tab) -> RunOptions {
+    RunOptions {
+        language: tab.language,
+        inspect: tab.inspect,
+        limits: tab.limits.unwrap_or_else(|| global_limits(app)),
+        timing: tab.timing,
+        coverage: tab.coverage,
     }
-    let tabs_json = serde_json::to_value(&tabs_data).unwrap();
-    let _ = app.svelte().set(STORE_NAME, TABS_KEY, tabs_json);
+}
+
+/// Limits of tabs without their own.
+fn global_limits(app: &tauri::AppHandle) -> Limits {
+    app.svelte()
+        .try_get::<Limits>(SETTINGS_STORE_NAME, LIMITS_KEY)
+        .unwrap_or_default()
+}
 
+/// Applies `update` to the tab in the store. The tabs are read right before
+/// writing, so edits made while the snippet ran are kept.
+fn write_tab(
+    app: &tauri::AppHandle,
+    tab_id: &str,
+    update: impl FnOnce(&mut Tab),
+) -> Result<(), Error> {
+    let mut tabs_data = app.svelte().try_get::<Vec<Tab>>(STORE_NAME, TABS_KEY)?;
+    if let Some(tab) = tabs_data.iter_mut().find(|tab| tab.id == tab_id) {
+        update(tab);
+    }
+    let tabs_json = serde_json::to_value(&tabs_data)?;
+    app.svelte().set(STORE_NAME, TABS_KEY, tabs_json)?;
     Ok(())
 }
 
+/// Writes an evaluation into the tab. With `lines`, the results, diagnostics
+/// and errors outside of those lines are kept.
+fn update_tab(tab: &mut Tab, evaluation: Evaluation, lines: Option<RangeInclusive<usize>>) {
+    let (results, diagnostics, errors, tests, coverage) = match lines {
+        Some(lines) => {
+            let mut results = std::mem::take(&mut tab.results);
+            results.retain(|entry| !lines.contains(&entry.line));
+            results.extend(evaluation.results);
+            results.sort_by_key(|entry| (entry.line, entry.start, entry.end, entry.hit));
+
+            let mut diagnostics = std::mem::take(&mut tab.diagnostics);
+            diagnostics.retain(|diagnostic| {
+                diagnostic
+                    .span
+                    .as_ref()
+                    .is_some_and(|span| !lines.contains(&span.line))
+            });
+            diagnostics.extend(evaluation.diagnostics);
+            // The errors of the other lines are only kept as diagnostics.
+            let errors = diagnostics
+                .iter()
+                .map(Diagnostic::to_string)
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            let mut tests = std::mem::take(&mut tab.tests);
+            tests.retain(|test| !lines.contains(&test.line));
+            tests.extend(evaluation.tests);
+            tests.sort_by_key(|test| test.start);
+
+            let mut coverage = std::mem::take(&mut tab.coverage_ranges);
+            coverage.retain(|range| !lines.contains(&range.line));
+            coverage.extend(evaluation.coverage);
+            coverage.sort_by_key(|range| (range.start, std::cmp::Reverse(range.end)));
+            (results, diagnostics, errors, tests, coverage)
+        }
+        None => (
+            evaluation.results,
+            evaluation.diagnostics,
+            evaluation.errors.join("\n"),
+            evaluation.tests,
+            evaluation.coverage,
+        ),
+    };
+
+    let line_hits = collect_line_hits(&results);
+    tab.result = transform_to_result(&line_hits, &diagnostics);
+    tab.hits = line_hits;
+    tab.results = results;
+    tab.errors = errors;
+    tab.diagnostics = diagnostics;
+    tab.tests = tests;
+    tab.coverage_ranges = coverage;
+}
+
+/// Validates a JSON scratchpad, the parsed value is shown on the first line.
+fn evaluate_json(source_text: &str) -> (String, String) {
+    if source_text.trim().is_empty() {
+        return (String::new(), String::new());
+    }
+
+    match serde_json::from_str::<Value>(source_text) {
+        Ok(value) => (format!("{}\n", value_to_string(&value)), String::new()),
+        Err(err) => (String::new(), err.to_string()),
+    }
+}
 
 #[tauri::command]
 fn show_window(app: tauri::AppHandle) {
-    app.get_webview_window("main").unwrap().show().unwrap();
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.show();
+    }
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -173,7 +251,14 @@ pub fn run() {
         .plugin(tauri_plugin_os::init())
         .plugin(plugins::tauri_traffic_light_positioner_plugin::init())
         .plugin(tauri_plugin_svelte::init())
-        .invoke_handler(tauri::generate_handler![handle_editor_changes, show_window])
+        .manage(Sessions::default())
+        .manage(Runs::default())
+        .invoke_handler(tauri::generate_handler![
+            handle_editor_changes,
+            run_selection,
+            reset_session,
+            show_window
+        ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
@@ -0,0 +1,53 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// Resources a single run of a snippet may use. Set globally in the store and
+/// overridden per tab.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Limits {
+    // Wall clock time for the module and its pending timers and promises.
+    pub timeout_ms: u64,
+    // V8 heap size, the run is terminated when it is exhausted.
+    pub max_heap_mb: usize,
+    // Reported values kept per run across all expressions, each of which
+    // keeps the values of its last few hits. Values past it are counted but
+    // dropped.
+    pub max_results: usize,
+    // Iterations a single loop may run before it is aborted, `None` leaves
+    // loops unguarded.
+    pub max_loop_iterations: Option<usize>,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            timeout_ms: 2_000,
+            max_heap_mb: 512,
+            max_results: 10_000,
+            max_loop_iterations: None,
+        }
+    }
+}
+
+impl Limits {
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_ms)
+    }
+
+    pub fn max_heap_size(&self) -> usize {
+        self.max_heap_mb * 1024 * 1024
+    }
+
+    /// Script that installs the limits enforced by `Xtal` and `XtalLoop`.
+    pub fn to_script(&self) -> String {
+        let max_loop_iterations = self
+            .max_loop_iterations
+            .map_or_else(|| "Infinity".to_string(), |count| count.to_string());
+        format!(
+            "globalThis.XtalLimits = {{ maxResults: {}, maxLoopIterations: {} }};",
+            self.max_results, max_loop_iterations
+        )
+    }
+}
@@ -0,0 +1,60 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use rustyscript::deno_core::v8::IsolateHandle;
+
+/// Latest run of every tab. Each edit starts a new revision, older runs are
+/// terminated and their results dropped.
+#[derive(Default)]
+pub struct Runs {
+    runs: Mutex<HashMap<String, Run>>,
+}
+
+#[derive(Default)]
+struct Run {
+    revision: u64,
+    // Isolate of the run in flight, until it has finished.
+    isolate: Option<IsolateHandle>,
+}
+
+impl Runs {
+    /// Starts a new revision for the tab and terminates the one in flight.
+    pub fn start(&self, tab_id: &str) -> u64 {
+        let mut runs = self.lock();
+        let run = runs.entry(tab_id.to_string()).or_default();
+        if let Some(isolate) = run.isolate.take() {
+            isolate.terminate_execution();
+        }
+        run.revision += 1;
+        run.revision
+    }
+
+    /// Registers the isolate a revision runs in, so a newer edit can stop it.
+    /// A revision that has already been superseded is terminated right away.
+    pub fn attach(&self, tab_id: &str, revision: u64, isolate: IsolateHandle) {
+        let mut runs = self.lock();
+        match runs.get_mut(tab_id) {
+            Some(run) if run.revision == revision => run.isolate = Some(isolate),
+            _ => {
+                isolate.terminate_execution();
+            }
+        }
+    }
+
+    /// Calls `commit` if `revision` is still the tab's latest run. The lock is
+    /// held meanwhile, so a newer run can't start and write in between.
+    pub fn finish<T>(&self, tab_id: &str, revision: u64, commit: impl FnOnce() -> T) -> Option<T> {
+        let mut runs = self.lock();
+        match runs.get_mut(tab_id) {
+            Some(run) if run.revision == revision => {
+                run.isolate = None;
+                Some(commit())
+            }
+            _ => None,
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, Run>> {
+        self.runs.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
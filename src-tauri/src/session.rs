@@ -0,0 +1,192 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread;
+
+use rustyscript::deno_core::v8::IsolateHandle;
+
+use crate::ast_replacer::inspect::InspectOptions;
+use crate::error::Error;
+use crate::evaluator::{
+    await_terminated, create_runtime, evaluate_script, install_helpers, Evaluation, RunOptions,
+};
+use crate::limits::Limits;
+
+/// Long-lived runtimes of the tabs in session mode, keyed by tab id.
+#[derive(Default)]
+pub struct Sessions {
+    sessions: Mutex<HashMap<String, Session>>,
+}
+
+/// A runtime owned by a dedicated worker thread. The thread lives until the
+/// session is dropped, which closes its command channel.
+struct Session {
+    commands: Sender<SessionCommand>,
+    isolate: IsolateHandle,
+    // Set before a run is terminated for taking too long, the worker clears
+    // it when the next run starts.
+    timed_out: Arc<AtomicBool>,
+    // Set by the worker when a run exhausted the heap. The runtime can't be
+    // used after that, the worker stops and the session is dropped.
+    heap_exhausted: Arc<AtomicBool>,
+}
+
+enum SessionCommand {
+    Run {
+        source_text: String,
+        options: RunOptions,
+        reply: Sender<Result<Evaluation, Error>>,
+    },
+}
+
+impl Sessions {
+    /// Evaluates a snippet in the tab's session, starting one if needed.
+    /// Runs longer than the timeout are terminated, the session survives them.
+    /// The heap limit is the one the session was started with, a run that
+    /// exhausts it ends the session.
+    /// `on_isolate` gets the session's isolate before the run starts.
+    pub fn run(
+        &self,
+        tab_id: &str,
+        source_text: String,
+        options: RunOptions,
+        on_isolate: impl FnOnce(IsolateHandle),
+    ) -> Result<Evaluation, Error> {
+        let limits = options.limits;
+        let (reply, response) = channel();
+        let mut spawned = None;
+        let (isolate, timed_out, heap_exhausted) = loop {
+            // Starting a runtime blocks for a while, it happens outside the
+            // lock so the other tabs can run and reset meanwhile.
+            if spawned.is_none() && !self.lock().contains_key(tab_id) {
+                spawned = Some(Session::spawn(limits)?);
+            }
+            let mut sessions = self.lock();
+            let session = match sessions.entry(tab_id.to_string()) {
+                // A concurrent run may have started one first, the spare
+                // session is dropped.
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => match spawned.take() {
+                    Some(session) => entry.insert(session),
+                    // Reset since it was looked up.
+                    None => continue,
+                },
+            };
+            on_isolate(session.isolate.clone());
+            let sent = session.commands.send(SessionCommand::Run {
+                source_text,
+                options,
+                reply,
+            });
+            let isolate = session.isolate.clone();
+            let timed_out = session.timed_out.clone();
+            let heap_exhausted = session.heap_exhausted.clone();
+            if sent.is_err() {
+                sessions.remove(tab_id);
+                return Err(Error::WorkerPanicked);
+            }
+            break (isolate, timed_out, heap_exhausted);
+        };
+
+        let result = match response.recv_timeout(limits.timeout()) {
+            Ok(result) => result,
+            Err(RecvTimeoutError::Timeout) => {
+                // Only the running script is terminated, the worker resets
+                // the isolate before the next run.
+                timed_out.store(true, Ordering::SeqCst);
+                isolate.terminate_execution();
+                await_terminated(&response, limits)
+            }
+            Err(RecvTimeoutError::Disconnected) => Err(Error::WorkerPanicked),
+        };
+
+        // A worker that is gone, stuck or out of heap is dropped, the next
+        // run starts a new session.
+        if matches!(result, Err(Error::WorkerPanicked | Error::Timeout(_)))
+            || heap_exhausted.load(Ordering::SeqCst)
+        {
+            self.lock().remove(tab_id);
+        }
+        result
+    }
+
+    /// Throws away the tab's session, the next run starts from a fresh runtime.
+    pub fn reset(&self, tab_id: &str) {
+        if let Some(session) = self.lock().remove(tab_id) {
+            // A run still in progress would keep the worker alive.
+            session.isolate.terminate_execution();
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, Session>> {
+        self.sessions.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Session {
+    fn spawn(limits: Limits) -> Result<Self, Error> {
+        let (commands, requests) = channel();
+        let (tx_handle, rx_handle) = channel();
+        let timed_out = Arc::new(AtomicBool::new(false));
+        let worker_timed_out = timed_out.clone();
+        let heap_exhausted = Arc::new(AtomicBool::new(false));
+        let worker_heap_exhausted = heap_exhausted.clone();
+
+        thread::spawn(move || {
+            let prepared = create_runtime(limits).and_then(|mut runtime| {
+                install_helpers(&mut runtime, InspectOptions::default(), limits)?;
+                Ok(runtime)
+            });
+            let mut runtime = match prepared {
+                Ok(runtime) => runtime,
+                Err(err) => {
+                    let _ = tx_handle.send(Err(err));
+                    return;
+                }
+            };
+
+            let ts_handle = runtime
+                .deno_runtime()
+                .v8_isolate()
+                .thread_safe_handle();
+            let _ = tx_handle.send(Ok(ts_handle));
+
+            // Runs until the session is dropped and the channel closes.
+            for command in requests {
+                match command {
+                    SessionCommand::Run {
+                        source_text,
+                        options,
+                        reply,
+                    } => {
+                        worker_timed_out.store(false, Ordering::SeqCst);
+                        let evaluation = evaluate_script(
+                            &mut runtime,
+                            &source_text,
+                            options,
+                            &worker_timed_out,
+                        );
+                        // The near heap limit callback leaves the runtime
+                        // failing every call it makes from now on.
+                        let exhausted = runtime.heap_exhausted_token().is_cancelled();
+                        worker_heap_exhausted.store(exhausted, Ordering::SeqCst);
+                        let _ = reply.send(evaluation);
+                        if exhausted {
+                            break;
+                        }
+                    }
+                }
+            }
+        });
+
+        let isolate = rx_handle.recv().map_err(|_| Error::WorkerPanicked)??;
+        Ok(Self {
+            commands,
+            isolate,
+            timed_out,
+            heap_exhausted,
+        })
+    }
+}
@@ -1,5 +1,11 @@
+use oxc_span::SourceType;
 use serde::{Deserialize, Serialize};
 
+use crate::ast_replacer::inspect::InspectOptions;
+use crate::ast_replacer::utils::{CoverageRange, LineHits, ResultEntry, TestResult};
+use crate::diagnostic::Diagnostic;
+use crate::limits::Limits;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Tab {
     pub id: String,
@@ -8,4 +14,95 @@ pub struct Tab {
     pub active: bool,
     pub result: String,
     pub errors: String,
-}
\ No newline at end of file
+    // Structured errors, mapped back to the user's source.
+    #[serde(default)]
+    pub diagnostics: Vec<Diagnostic>,
+    // Tabs stored before the language setting existed are JavaScript.
+    #[serde(default)]
+    pub language: Language,
+    // Per-expression hit counts and latest values, the UI expands these from the
+    // collapsed `result` column.
+    #[serde(default)]
+    pub hits: Vec<LineHits>,
+    // Every reported value with its location, kind and hit index.
+    #[serde(default)]
+    pub results: Vec<ResultEntry>,
+    // Depth and length limits values are rendered with.
+    #[serde(default)]
+    pub inspect: InspectOptions,
+    // Session tabs keep one runtime between runs, like a notebook.
+    #[serde(default)]
+    pub session: bool,
+    // Inline `test` blocks of the last run.
+    #[serde(default)]
+    pub tests: Vec<TestResult>,
+    // Overrides the global limits for this tab.
+    #[serde(default)]
+    pub limits: Option<Limits>,
+    // Shows how long every expression took next to its value.
+    #[serde(default)]
+    pub timing: bool,
+    // Marks the statements and branches that didn't run.
+    #[serde(default)]
+    pub coverage: bool,
+    // Coverage of the last run, in coverage mode.
+    #[serde(default)]
+    pub coverage_ranges: Vec<CoverageRange>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    #[default]
+    JavaScript,
+    TypeScript,
+    Jsx,
+    Tsx,
+    Json,
+}
+
+impl Language {
+    /// Language of a file by its extension, e.g. `ts` or `jsx`.
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension {
+            "js" | "mjs" | "cjs" => Some(Language::JavaScript),
+            "ts" | "mts" | "cts" => Some(Language::TypeScript),
+            "jsx" => Some(Language::Jsx),
+            "tsx" => Some(Language::Tsx),
+            "json" => Some(Language::Json),
+            _ => None,
+        }
+    }
+
+    /// File name the snippet is parsed and transformed as.
+    pub fn file_name(self) -> &'static str {
+        match self {
+            Language::JavaScript => "snippet.js",
+            Language::TypeScript => "snippet.ts",
+            Language::Jsx => "snippet.jsx",
+            Language::Tsx => "snippet.tsx",
+            Language::Json => "snippet.json",
+        }
+    }
+
+    pub fn source_type(self) -> SourceType {
+        match self {
+            Language::JavaScript | Language::Json => SourceType::mjs(),
+            Language::TypeScript => SourceType::ts(),
+            Language::Jsx => SourceType::jsx(),
+            Language::Tsx => SourceType::tsx(),
+        }
+        .with_module(true)
+    }
+
+    /// Whether the parsed program has to go through oxc_transformer before
+    /// it can be executed.
+    pub fn needs_transform(self) -> bool {
+        matches!(self, Language::TypeScript | Language::Jsx | Language::Tsx)
+    }
+
+    /// JSON scratchpads are validated, never instrumented or executed.
+    pub fn is_executable(self) -> bool {
+        self != Language::Json
+    }
+}
@@ -0,0 +1,67 @@
+use pracc_js_lib::ast_replacer::utils::{CoverageKind, CoverageRange};
+use pracc_js_lib::evaluator::{evaluate, RunOptions};
+
+fn coverage(source_text: &str) -> Vec<CoverageRange> {
+    let options = RunOptions {
+        coverage: true,
+        ..Default::default()
+    };
+    let evaluation = evaluate(source_text, options).expect("evaluate snippet");
+    assert!(!evaluation.has_errors(), "snippet failed: {:?}", evaluation.errors);
+    evaluation.coverage
+}
+
+/// Hits of the range covering exactly `text` in `source_text`.
+fn hits(ranges: &[CoverageRange], source_text: &str, text: &str, kind: CoverageKind) -> usize {
+    let start = source_text.find(text).expect("text in snippet");
+    let end = start + text.len();
+    ranges
+        .iter()
+        .find(|range| range.kind == kind && range.start == start && range.end == end)
+        .unwrap_or_else(|| panic!("no {:?} range for `{}` in {:?}", kind, text, ranges))
+        .hits
+}
+
+#[test]
+fn statements_count_their_runs() {
+    let source_text = "let total = 0;\nfor (let i = 0; i < 3; i++) total += i;\n";
+    let ranges = coverage(source_text);
+
+    assert_eq!(hits(&ranges, source_text, "let total = 0;", CoverageKind::Statement), 1);
+    assert_eq!(hits(&ranges, source_text, "total += i;", CoverageKind::Statement), 3);
+}
+
+#[test]
+fn untaken_branches_have_no_hits() {
+    let source_text = "const x = 1;\nif (x > 0) { x } else { -x }\nx > 0 ? 'yes' : 'no'\nx || 'fallback'\n";
+    let ranges = coverage(source_text);
+
+    assert_eq!(hits(&ranges, source_text, "{ x }", CoverageKind::Branch), 1);
+    assert_eq!(hits(&ranges, source_text, "{ -x }", CoverageKind::Branch), 0);
+    assert_eq!(hits(&ranges, source_text, "'yes'", CoverageKind::Branch), 1);
+    assert_eq!(hits(&ranges, source_text, "'no'", CoverageKind::Branch), 0);
+    assert_eq!(hits(&ranges, source_text, "'fallback'", CoverageKind::Branch), 0);
+}
+
+#[test]
+fn nothing_is_collected_without_coverage_mode() {
+    let evaluation = evaluate("1 + 1", RunOptions::default()).expect("evaluate snippet");
+
+    assert!(evaluation.coverage.is_empty());
+}
+
+#[test]
+fn single_statement_bodies_are_counted_once() {
+    let source_text = "const x = 1;\nif (x > 0) x + 1;\nx && x + 2\n";
+    let ranges = coverage(source_text);
+
+    assert_eq!(hits(&ranges, source_text, "x + 1;", CoverageKind::Branch), 1);
+    assert_eq!(hits(&ranges, source_text, "x + 2", CoverageKind::Branch), 1);
+    let spans = ranges
+        .iter()
+        .map(|range| (range.start, range.end))
+        .collect::<std::collections::HashSet<_>>();
+    assert_eq!(spans.len(), ranges.len(), "ranges share a span: {:?}", ranges);
+    let left = source_text.rfind("x &&").expect("logical expression");
+    assert!(!ranges.iter().any(|range| range.start == left && range.end == left + 1));
+}
@@ -0,0 +1,7 @@
+let a = 1;
+Xtal(1, 4, 9, a);
+Xtal(2, 11, 16, a = 2);
+Xtal(3, 17, 23, a += 3);
+const point = {};
+Xtal(4, 30, 40, point);
+Xtal(5, 42, 53, point.x = 4);
@@ -0,0 +1,5 @@
+let a = 1;
+a = 2
+a += 3
+const point = {};
+point.x = 4
@@ -0,0 +1,5 @@
+const value = await Promise.resolve(42);
+Xtal(1, 6, 39, value);
+Xtal(2, 41, 46, value);
+Xtal(3, 47, 101, await new Promise((resolve) => setTimeout(resolve, 0)));
+Xtal(4, 102, 131, await Promise.resolve("done"));
@@ -0,0 +1,4 @@
+const value = await Promise.resolve(42);
+value
+await new Promise((resolve) => setTimeout(resolve, 0))
+await Promise.resolve("done")
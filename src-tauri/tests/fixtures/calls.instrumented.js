@@ -0,0 +1,4 @@
+const double = (x) => x * 2;
+Xtal(1, 6, 27, double);
+Xtal(2, 29, 39, double(21));
+Xtal(3, 40, 59, "a", 1);
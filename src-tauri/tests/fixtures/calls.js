@@ -0,0 +1,3 @@
+const double = (x) => x * 2;
+double(21)
+console.log("a", 1)
@@ -0,0 +1,3 @@
+const x = 3;
+Xtal(1, 6, 11, x);
+Xtal(2, 13, 36, x > 2 ? "big" : "small");
@@ -0,0 +1,2 @@
+const x = 3;
+x > 2 ? "big" : "small"
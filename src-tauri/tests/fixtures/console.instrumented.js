@@ -0,0 +1,3 @@
+Xtal(1, 0, 19, "a", 1);
+Xtal(2, 20, 43, "careful");
+Xtal(3, 44, 66, { a: 1 });
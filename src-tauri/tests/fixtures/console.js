@@ -0,0 +1,3 @@
+console.log("a", 1)
+console.warn("careful")
+console.info({ a: 1 })
@@ -0,0 +1,5 @@
+const a = 1;
+Xtal(1, 6, 11, a);
+let [b, c] = [2, 3];
+Xtal(2, 17, 32, b, c);
+Xtal(3, 34, 43, a + b + c);
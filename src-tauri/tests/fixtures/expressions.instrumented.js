@@ -0,0 +1,11 @@
+Xtal(1, 0, 5, 123n);
+Xtal(2, 6, 11, null);
+Xtal(3, 12, 32, new Map([["a", 1]]));
+Xtal(4, 33, 39, /re/g);
+const arr = [1, 2];
+Xtal(5, 46, 58, arr);
+Xtal(6, 60, 67, arr[0]);
+const obj = { nested: { x: 2 } };
+Xtal(7, 74, 100, obj);
+Xtal(8, 102, 117, obj?.nested?.x);
+Xtal(9, 118, 123, this);
@@ -0,0 +1,9 @@
+123n;
+null;
+new Map([["a", 1]]);
+/re/g;
+const arr = [1, 2];
+arr[0];
+const obj = { nested: { x: 2 } };
+obj?.nested?.x;
+this;
@@ -0,0 +1,12 @@
+const a = {};
+Xtal(1, 6, 12, a);
+Xtal(2, 14, 25, a.self = a);
+Xtal(3, 26, 28, a);
+Xtal(4, 29, 73, { one: { two: { three: { four: {} } } } });
+Xtal(5, 74, 115, Array.from({ length: 103 }, (_, i) => i));
+const broken = { get boom() {
+	throw new Error("nope");
+} };
+Xtal(6, 122, 174, broken);
+Xtal(7, 176, 183, broken);
+Xtal(8, 184, 190, 1 + 1);
@@ -0,0 +1,8 @@
+const a = {};
+a.self = a;
+a;
+({ one: { two: { three: { four: {} } } } });
+Array.from({ length: 103 }, (_, i) => i);
+const broken = { get boom() { throw new Error("nope"); } };
+broken;
+1 + 1;
@@ -0,0 +1,5 @@
+Xtal(1, 0, 5, 1 + 2);
+Xtal(2, 6, 13, "hello");
+Xtal(3, 14, 16, 42);
+Xtal(4, 17, 22, true);
+Xtal(5, 23, 29, [1, 2]);
@@ -0,0 +1,5 @@
+1 + 2
+"hello"
+42
+true;
+[1, 2]
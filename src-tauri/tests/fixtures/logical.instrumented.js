@@ -0,0 +1,5 @@
+const a = 0;
+Xtal(1, 6, 11, a);
+Xtal(2, 13, 28, a || "fallback");
+Xtal(3, 29, 43, a ?? "nullish");
+Xtal(4, 44, 56, a && "never");
@@ -0,0 +1,4 @@
+const a = 0;
+a || "fallback"
+a ?? "nullish"
+a && "never"
@@ -0,0 +1,7 @@
+{
+	XtalLoopEnter(1, 0);
+	for (let i = 0; i < 3; i++) {
+		XtalLoop(1, 0);
+		Xtal(2, 32, 37, i * 2);
+	}
+}
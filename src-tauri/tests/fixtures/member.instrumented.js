@@ -0,0 +1,7 @@
+const point = {
+	x: 1,
+	y: 2
+};
+Xtal(1, 6, 28, point);
+Xtal(2, 30, 37, point.x);
+Xtal(3, 38, 45, Math.PI);
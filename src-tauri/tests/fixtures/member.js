@@ -0,0 +1,3 @@
+const point = { x: 1, y: 2 };
+point.x
+Math.PI
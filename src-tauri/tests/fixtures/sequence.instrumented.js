@@ -0,0 +1,3 @@
+let s = 0;
+Xtal(1, 4, 9, s);
+Xtal(2, 11, 22, (s++, s++, s));
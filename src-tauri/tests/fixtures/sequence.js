@@ -0,0 +1,2 @@
+let s = 0;
+s++, s++, s
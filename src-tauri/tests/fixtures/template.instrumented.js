@@ -0,0 +1,3 @@
+const name = "world";
+Xtal(1, 6, 20, name);
+Xtal(2, 22, 37, `hello ${name}`);
@@ -0,0 +1,2 @@
+const name = "world";
+`hello ${name}`
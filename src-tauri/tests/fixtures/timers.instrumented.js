@@ -0,0 +1,6 @@
+setTimeout(() => {
+	Xtal(2, 21, 35, "later".length);
+}, 0);
+queueMicrotask(() => {
+	Xtal(5, 67, 72, 1 + 1);
+});
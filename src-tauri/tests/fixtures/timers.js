@@ -0,0 +1,6 @@
+setTimeout(() => {
+  "later".length
+}, 0)
+queueMicrotask(() => {
+  1 + 1
+})
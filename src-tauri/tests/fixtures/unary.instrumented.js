@@ -0,0 +1,6 @@
+const n = 5;
+Xtal(1, 6, 11, n);
+Xtal(2, 13, 15, -n);
+Xtal(3, 16, 18, !n);
+Xtal(4, 19, 27, typeof n);
+Xtal(5, 28, 34, void 0);
@@ -0,0 +1,5 @@
+const n = 5;
+-n
+!n
+typeof n
+void 0
@@ -0,0 +1,6 @@
+let i = 0;
+Xtal(1, 4, 9, i);
+Xtal(2, 11, 14, ++i);
+Xtal(3, 15, 18, ++i);
+Xtal(4, 19, 22, --i);
+Xtal(5, 23, 24, i);
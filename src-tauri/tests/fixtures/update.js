@@ -0,0 +1,5 @@
+let i = 0;
+i++
+++i
+i--
+i
@@ -0,0 +1,19 @@
+Xtal(1, 0, 10, undefined);
+Xtal(2, 11, 15, NaN);
+Xtal(3, 16, 19, -0);
+Xtal(4, 20, 31, 10n ** 20n);
+Xtal(5, 32, 45, Symbol("id"));
+Xtal(6, 46, 83, new Map([["a", 1], ["b", { c: 2 }]]));
+Xtal(7, 84, 104, new Set([1, "two"]));
+class Point {
+	constructor(x, y) {
+		Xtal(10, 145, 156, this.x = x);
+		Xtal(11, 161, 172, this.y = y);
+	}
+}
+Xtal(14, 179, 195, new Point(1, 2));
+Xtal(15, 196, 219, [
+	undefined,
+	null,
+	NaN
+]);
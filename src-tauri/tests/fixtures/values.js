@@ -0,0 +1,15 @@
+undefined;
+NaN;
+-0;
+10n ** 20n;
+Symbol("id");
+new Map([["a", 1], ["b", { c: 2 }]]);
+new Set([1, "two"]);
+class Point {
+  constructor(x, y) {
+    this.x = x;
+    this.y = y;
+  }
+}
+new Point(1, 2);
+[undefined, null, NaN];
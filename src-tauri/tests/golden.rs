@@ -0,0 +1,89 @@
+//! Golden files for the instrumentation and the result column. Every
+//! `tests/fixtures/<name>.js` is compared against `<name>.instrumented.js`,
+//! the code `AstReplacer` generates, and `<name>.result.txt`, the result
+//! column of running it. Run with `UPDATE_GOLDEN=1` to rewrite them.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use oxc_allocator::Allocator;
+use oxc_codegen::CodeGenerator;
+use oxc_parser::Parser;
+use oxc_span::SourceType;
+use pracc_js_lib::ast_replacer::lib::{AstReplacer, AstReplacerOptions};
+use pracc_js_lib::ast_replacer::line_index::LineIndex;
+use pracc_js_lib::evaluator::{evaluate, RunOptions};
+
+fn fixtures() -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let mut fixtures = fs::read_dir(dir)
+        .expect("fixtures directory")
+        .map(|entry| entry.expect("fixture entry").path())
+        .filter(|path| {
+            let name = path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+            name.ends_with(".js") && !name.ends_with(".instrumented.js")
+        })
+        .collect::<Vec<_>>();
+    fixtures.sort();
+    fixtures
+}
+
+fn instrument(source_text: &str) -> String {
+    let allocator = Allocator::default();
+    let ret = Parser::new(&allocator, source_text, SourceType::mjs()).parse();
+    assert!(ret.errors.is_empty(), "fixture doesn't parse: {:?}", ret.errors);
+
+    let mut program = ret.program;
+    let line_index = LineIndex::new(source_text);
+    AstReplacer::new(&allocator, &line_index, AstReplacerOptions::default()).build(&mut program);
+    CodeGenerator::new().build(&program).code
+}
+
+/// Compares `actual` with the golden file, or rewrites it when updating.
+/// Mismatches are collected so a run reports all of them at once.
+fn check(path: &Path, actual: &str, mismatches: &mut Vec<String>) {
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(path, actual).expect("write golden file");
+        return;
+    }
+
+    let expected = fs::read_to_string(path).unwrap_or_default();
+    if expected != actual {
+        mismatches.push(format!(
+            "{}\n--- expected\n{}\n--- actual\n{}",
+            path.display(),
+            expected,
+            actual
+        ));
+    }
+}
+
+#[test]
+fn fixtures_match_golden_files() {
+    let mut mismatches = Vec::new();
+
+    for fixture in fixtures() {
+        let source_text = fs::read_to_string(&fixture).expect("read fixture");
+
+        check(
+            &fixture.with_extension("instrumented.js"),
+            &instrument(&source_text),
+            &mut mismatches,
+        );
+
+        let evaluation =
+            evaluate(&source_text, RunOptions::default()).expect("evaluate fixture");
+        check(
+            &fixture.with_extension("result.txt"),
+            &evaluation.result_column(),
+            &mut mismatches,
+        );
+    }
+
+    assert!(
+        mismatches.is_empty(),
+        "{} golden file(s) differ, rerun with UPDATE_GOLDEN=1 to accept:\n\n{}",
+        mismatches.len(),
+        mismatches.join("\n\n")
+    );
+}
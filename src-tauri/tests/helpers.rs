@@ -0,0 +1,26 @@
+use pracc_js_lib::evaluator::{evaluate, RunOptions};
+
+#[test]
+fn global_helpers_report_through_xtal() {
+    let source_text = "test('adds', () => {\n  expect(1 + 1).toBe(2)\n})\n";
+    let evaluation = evaluate(source_text, RunOptions::default()).expect("evaluate snippet");
+
+    assert!(!evaluation.has_errors(), "snippet failed: {:?}", evaluation.errors);
+    assert_eq!(evaluation.tests.len(), 1);
+    assert!(evaluation.tests[0].passed);
+}
+
+#[test]
+fn bindings_named_like_helpers_are_left_alone() {
+    let source_text = "\
+function check(expect, test) { return expect(1) + test(2) }
+check((x) => x, (x) => x)
+const run = (bench) => bench()
+run(() => 3)
+";
+    let evaluation = evaluate(source_text, RunOptions::default()).expect("evaluate snippet");
+
+    assert!(!evaluation.has_errors(), "snippet failed: {:?}", evaluation.errors);
+    assert!(evaluation.tests.is_empty());
+    assert_eq!(evaluation.result_column(), "\n3\n[Function: run]\n3\n");
+}
@@ -0,0 +1,130 @@
+use pracc_js_lib::ast_replacer::inspect::Inspected;
+use serde_json::{json, Value};
+
+/// Renders a value the way `XtalInspect` tags it in the runtime.
+fn render(value: Value) -> String {
+    serde_json::from_value::<Inspected>(value)
+        .expect("inspected value")
+        .to_string()
+}
+
+#[test]
+fn primitives_render_like_node() {
+    assert_eq!(render(json!({ "type": "undefined" })), "undefined");
+    assert_eq!(render(json!({ "type": "null" })), "null");
+    assert_eq!(render(json!({ "type": "boolean", "value": true })), "true");
+    assert_eq!(render(json!({ "type": "number", "value": "-0" })), "-0");
+    assert_eq!(render(json!({ "type": "number", "value": "NaN" })), "NaN");
+    assert_eq!(render(json!({ "type": "bigint", "value": "123" })), "123n");
+    assert_eq!(
+        render(json!({ "type": "symbol", "description": "id" })),
+        "Symbol(id)"
+    );
+}
+
+#[test]
+fn strings_are_quoted_inside_values_only() {
+    let value = json!({ "type": "string", "value": "it's" });
+
+    assert_eq!(render(value.clone()), "'it\\'s'");
+    assert_eq!(
+        serde_json::from_value::<Inspected>(value).unwrap().render(),
+        "it's"
+    );
+}
+
+#[test]
+fn functions_and_classes_show_their_names() {
+    let function =
+        |name: &str, class: bool| json!({ "type": "function", "name": name, "class": class });
+
+    assert_eq!(render(function("foo", false)), "[Function: foo]");
+    assert_eq!(render(function("", false)), "[Function (anonymous)]");
+    assert_eq!(render(function("X", true)), "[class X]");
+    assert_eq!(render(function("", true)), "[class (anonymous)]");
+}
+
+#[test]
+fn collections_show_their_size() {
+    let map = json!({
+        "type": "map",
+        "entries": [[{ "type": "string", "value": "a" }, { "type": "number", "value": "1" }]],
+        "more": 1,
+    });
+    let set = json!({ "type": "set", "values": [] });
+    let typed = json!({ "type": "typedarray", "constructor": "Uint8Array", "items": ["1", "2"] });
+
+    assert_eq!(render(map), "Map(2) { 'a' => 1, ... 1 more item }");
+    assert_eq!(render(set), "Set(0) {}");
+    assert_eq!(render(typed), "Uint8Array(2) [ 1, 2 ]");
+}
+
+#[test]
+fn objects_show_their_prototype() {
+    let entries = json!([["a", { "type": "number", "value": "1" }], ["b-c", { "type": "null" }]]);
+
+    assert_eq!(
+        render(json!({ "type": "object", "constructor": "Object", "entries": entries })),
+        "{ a: 1, 'b-c': null }"
+    );
+    assert_eq!(
+        render(json!({ "type": "object", "constructor": "Point", "entries": [] })),
+        "Point {}"
+    );
+    assert_eq!(
+        render(json!({ "type": "object", "constructor": null, "entries": [] })),
+        "[Object: null prototype] {}"
+    );
+}
+
+#[test]
+fn errors_dates_and_regexps() {
+    assert_eq!(
+        render(json!({ "type": "error", "name": "TypeError", "message": "boom" })),
+        "[TypeError: boom]"
+    );
+    assert_eq!(
+        render(json!({ "type": "date", "value": "2024-01-01T00:00:00.000Z" })),
+        "2024-01-01T00:00:00.000Z"
+    );
+    assert_eq!(
+        render(json!({ "type": "regexp", "value": "/a+/g" })),
+        "/a+/g"
+    );
+}
+
+#[test]
+fn self_references_are_marked() {
+    let value = json!({
+        "type": "ref",
+        "id": 1,
+        "value": {
+            "type": "object",
+            "constructor": "Object",
+            "entries": [["self", { "type": "circular", "id": 1 }]],
+        },
+    });
+
+    assert_eq!(render(value), "<ref *1> { self: [Circular *1] }");
+}
+
+#[test]
+fn values_past_the_depth_keep_their_label() {
+    let value = json!({
+        "type": "array",
+        "items": [{ "type": "truncated", "label": "Array" }, { "type": "truncated", "label": "Point" }],
+    });
+
+    assert_eq!(render(value), "[ [Array], [Point] ]");
+}
+
+#[test]
+fn cut_off_items_and_characters_are_counted() {
+    let one = json!({ "type": "array", "items": [{ "type": "number", "value": "1" }], "more": 1 });
+    let many = json!({ "type": "array", "items": [], "more": 3 });
+    let string = json!({ "type": "string", "value": "abc", "more": 7 });
+
+    assert_eq!(render(one), "[ 1, ... 1 more item ]");
+    assert_eq!(render(many), "[ ... 3 more items ]");
+    assert_eq!(render(string), "'abc'... 7 more characters");
+}
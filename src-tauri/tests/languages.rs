@@ -0,0 +1,27 @@
+use pracc_js_lib::evaluator::{evaluate, RunOptions};
+use pracc_js_lib::tab::Language;
+
+fn result_lines(source_text: &str, language: Language) -> Vec<String> {
+    let options = RunOptions {
+        language,
+        ..Default::default()
+    };
+    let evaluation = evaluate(source_text, options).expect("evaluate snippet");
+    assert!(!evaluation.has_errors(), "snippet failed: {:?}", evaluation.errors);
+    evaluation.result_column().lines().map(str::to_string).collect()
+}
+
+#[test]
+fn typescript_expressions_report_their_value() {
+    let lines = result_lines("const n: number | null = 1;\nn!;\nn as number;\n", Language::TypeScript);
+
+    assert_eq!(lines, ["1", "1", "1"]);
+}
+
+#[test]
+fn jsx_elements_report_their_value() {
+    let lines = result_lines("<div>hi</div>;\n<><b /></>;\n", Language::Tsx);
+
+    assert!(lines[0].contains("div"), "unexpected results: {:?}", lines);
+    assert!(lines[1].contains("Fragment"), "unexpected results: {:?}", lines);
+}
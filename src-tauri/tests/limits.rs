@@ -0,0 +1,45 @@
+use pracc_js_lib::evaluator::{evaluate, RunOptions};
+use pracc_js_lib::limits::Limits;
+
+fn limited(limits: Limits) -> RunOptions {
+    RunOptions {
+        limits,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn heap_exhaustion_names_the_limit() {
+    let source_text = "const chunks = [];\nwhile (true) chunks.push(new Array(100000).fill(0));\n";
+    let limits = Limits {
+        max_heap_mb: 64,
+        ..Default::default()
+    };
+    let evaluation = evaluate(source_text, limited(limits)).expect("evaluate snippet");
+
+    assert!(
+        evaluation.errors.iter().any(|error| error.contains("Heap limit of 64 MB exceeded")),
+        "unexpected errors: {:?}",
+        evaluation.errors
+    );
+}
+
+fn timeout_line(source_text: &str) -> Option<usize> {
+    let limits = Limits {
+        timeout_ms: 200,
+        ..Default::default()
+    };
+    let evaluation = evaluate(source_text, limited(limits)).expect("evaluate snippet");
+    let diagnostic = evaluation
+        .diagnostics
+        .iter()
+        .find(|diagnostic| diagnostic.message.starts_with("Timed out"))
+        .unwrap_or_else(|| panic!("no timeout in {:?}", evaluation.errors));
+    diagnostic.span.map(|span| span.line)
+}
+
+#[test]
+fn timeouts_point_at_the_running_loop() {
+    assert_eq!(timeout_line("while (true) {}\n"), Some(1));
+    assert_eq!(timeout_line("let a = 1\nwhile (true) {}\n"), Some(2));
+}
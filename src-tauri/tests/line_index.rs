@@ -0,0 +1,85 @@
+use pracc_js_lib::ast_replacer::line_index::LineIndex;
+use pracc_js_lib::diagnostic::DiagnosticSpan;
+
+#[test]
+fn lines_and_columns_with_lf() {
+    let index = LineIndex::new("a\nbb\nccc");
+
+    assert_eq!(index.line_count(), 3);
+    assert_eq!(index.line(0), 1);
+    assert_eq!(index.line(2), 2);
+    assert_eq!(index.line(5), 3);
+    assert_eq!(index.column(3), 2);
+    assert_eq!(index.column(7), 3);
+}
+
+#[test]
+fn crlf_counts_as_a_single_line_break() {
+    let index = LineIndex::new("a\r\nbb\r\nc");
+
+    assert_eq!(index.line_count(), 3);
+    // The `\r` still belongs to the line it ends.
+    assert_eq!((index.line(1), index.column(1)), (1, 2));
+    assert_eq!((index.line(4), index.column(4)), (2, 2));
+    assert_eq!((index.line(7), index.column(7)), (3, 1));
+    assert_eq!(index.offset(3, 1), 7);
+}
+
+#[test]
+fn lone_cr_ends_a_line() {
+    let index = LineIndex::new("a\rb");
+
+    assert_eq!(index.line(2), 2);
+    assert_eq!(index.column(2), 1);
+}
+
+#[test]
+fn bom_is_not_part_of_the_first_line() {
+    let index = LineIndex::new("\u{FEFF}let x = 1;\ny");
+
+    assert_eq!((index.line(0), index.column(0)), (1, 1));
+    assert_eq!((index.line(3), index.column(3)), (1, 1));
+    assert_eq!(index.column(7), 5);
+    assert_eq!((index.line(14), index.column(14)), (2, 1));
+    assert_eq!(index.offset(1, 5), 7);
+}
+
+#[test]
+fn columns_are_counted_in_utf16_code_units() {
+    let source = "const s = 'é😀';\nx";
+    let index = LineIndex::new(source);
+
+    let e_acute = source.find('é').unwrap();
+    let emoji = source.find('😀').unwrap();
+    let closing_quote = source.rfind('\'').unwrap();
+
+    assert_eq!(index.column(e_acute), 12);
+    assert_eq!(index.column(emoji), 13);
+    assert_eq!(index.column(closing_quote), 15);
+    assert_eq!(index.offset(1, 15), closing_quote);
+    assert_eq!(index.offset(1, 13), emoji);
+    // Offsets inside a multi-byte character resolve to its start.
+    assert_eq!(index.column(e_acute + 1), 12);
+    assert_eq!(index.line(source.len() - 1), 2);
+}
+
+#[test]
+fn offsets_past_the_end_belong_to_the_last_line() {
+    let index = LineIndex::new("a\nb");
+
+    assert_eq!(index.line(100), 2);
+    assert_eq!(index.column(100), 2);
+    assert_eq!(index.offset(10, 1), 3);
+    assert_eq!(index.offset(1, 10), 1);
+}
+
+#[test]
+fn diagnostic_spans_end_in_utf16_columns() {
+    let source_text = "'😀é' + oops";
+    let index = LineIndex::new(source_text);
+    let offset = source_text.find("oops").expect("identifier");
+    let span = DiagnosticSpan::from_offset(&index, offset, "oops".len());
+
+    assert_eq!((span.line, span.column), (1, 9));
+    assert_eq!((span.end_line, span.end_column), (1, 13));
+}
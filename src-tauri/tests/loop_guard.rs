@@ -0,0 +1,38 @@
+use pracc_js_lib::evaluator::{evaluate, RunOptions};
+use pracc_js_lib::limits::Limits;
+
+fn guarded(max_loop_iterations: usize) -> RunOptions {
+    RunOptions {
+        limits: Limits {
+            max_loop_iterations: Some(max_loop_iterations),
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+#[test]
+fn nested_loops_count_every_entry_on_its_own() {
+    let source_text = "let n = 0;\nfor (let i = 0; i < 50; i++)\n  for (let j = 0; j < 50; j++) n++;\nn\n";
+    let evaluation = evaluate(source_text, guarded(100)).expect("evaluate snippet");
+
+    assert!(!evaluation.has_errors(), "snippet failed: {:?}", evaluation.errors);
+}
+
+#[test]
+fn endless_loops_are_aborted() {
+    let source_text = "while (true) {}\n";
+    let evaluation = evaluate(source_text, guarded(100)).expect("evaluate snippet");
+
+    assert!(evaluation.has_errors());
+    assert!(
+        evaluation.errors.iter().any(|error| error.contains("may never end")),
+        "unexpected errors: {:?}",
+        evaluation.errors
+    );
+}
+
+#[test]
+fn loops_are_unguarded_by_default() {
+    assert_eq!(Limits::default().max_loop_iterations, None);
+}
@@ -0,0 +1,79 @@
+use pracc_js_lib::ast_replacer::utils::{
+    format_duration, transform_to_result, value_to_string, LineHits,
+};
+use pracc_js_lib::diagnostic::{Diagnostic, DiagnosticKind, DiagnosticSpan, Severity};
+use serde_json::json;
+
+fn hits(line: usize, hits: usize, last_values: &[&str]) -> LineHits {
+    LineHits {
+        line,
+        column: 1,
+        start: 0,
+        end: 0,
+        hits,
+        last_values: last_values.iter().map(|value| value.to_string()).collect(),
+        duration: None,
+    }
+}
+
+#[test]
+fn values_land_on_their_lines() {
+    let result = transform_to_result(&[hits(1, 1, &["1"]), hits(3, 1, &["'a'"])], &[]);
+
+    assert_eq!(result, "1\n\n'a'\n");
+}
+
+#[test]
+fn repeated_sites_collapse_to_hit_count() {
+    let result = transform_to_result(&[hits(2, 4, &["1", "2", "3"])], &[]);
+
+    assert_eq!(result, "\n×4, last: 3\n");
+}
+
+#[test]
+fn timed_sites_show_their_last_duration() {
+    let timed = LineHits {
+        duration: Some(1.5),
+        ..hits(1, 3, &["1", "2"])
+    };
+
+    assert_eq!(transform_to_result(&[timed], &[]), "×3, last: 2 (1.50ms)\n");
+    assert_eq!(format_duration(0.0125), "12.5µs");
+}
+
+#[test]
+fn diagnostics_follow_the_values_of_their_line() {
+    let diagnostic = Diagnostic {
+        kind: DiagnosticKind::Runtime,
+        severity: Severity::Error,
+        message: "Error: boom".to_string(),
+        span: Some(DiagnosticSpan {
+            offset: 0,
+            length: 0,
+            line: 1,
+            column: 1,
+            end_line: 1,
+            end_column: 1,
+        }),
+        labels: Vec::new(),
+        help: None,
+        stack: Vec::new(),
+    };
+
+    let result = transform_to_result(&[hits(1, 1, &["1"])], &[diagnostic]);
+
+    assert_eq!(result, "1 ✖ Error: boom\n");
+}
+
+#[test]
+fn nothing_to_show_is_empty() {
+    assert_eq!(transform_to_result(&[], &[]), "");
+}
+
+#[test]
+fn json_values_render_plainly() {
+    assert_eq!(value_to_string(&json!(null)), "null");
+    assert_eq!(value_to_string(&json!("text")), "text");
+    assert_eq!(value_to_string(&json!([1, true, "a"])), "[1, true, a]");
+    assert_eq!(value_to_string(&json!({ "a": [1] })), "{\"a\": [1]}");
+}
@@ -0,0 +1,17 @@
+use pracc_js_lib::evaluator::{evaluate, RunOptions};
+
+#[test]
+fn shifted_errors_point_into_the_whole_snippet() {
+    let source_text = "const a = 1;\nnull.x\n";
+    let mut evaluation = evaluate(source_text, RunOptions::default()).expect("evaluate snippet");
+    assert!(evaluation.errors.iter().any(|error| error.contains("line 2:")));
+
+    evaluation.shift(10, 100);
+
+    assert_eq!(evaluation.errors.len(), evaluation.diagnostics.len());
+    assert!(
+        evaluation.errors.iter().any(|error| error.contains("line 12:")),
+        "unexpected errors: {:?}",
+        evaluation.errors
+    );
+}
@@ -0,0 +1,16 @@
+import { Store } from 'tauri-plugin-svelte';
+import type { Limits } from './tabs';
+
+// Limits every tab runs with unless it sets its own.
+export const defaultLimits: Limits = {
+    timeout_ms: 2000,
+    max_heap_mb: 512,
+    max_results: 10000,
+    max_loop_iterations: null,
+};
+
+export const settingsStore = new Store('settings', { limits: defaultLimits }, {
+    saveOnChange: true,
+    saveStrategy: 'debounce',
+    saveInterval: 500
+});
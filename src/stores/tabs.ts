@@ -1,5 +1,95 @@
 import { uuidv4 } from '../utils/uuid';
 import { Store } from 'tauri-plugin-svelte';
+import { invoke } from '@tauri-apps/api/core';
+
+export type Language = 'javascript' | 'typescript' | 'jsx' | 'tsx' | 'json';
+
+export type LineHits = {
+    line: number;
+    column: number;
+    start: number;
+    end: number;
+    hits: number;
+    last_values: string[];
+    duration?: number | null;
+}
+
+export type ResultEntry = {
+    line: number;
+    column: number;
+    end_line: number;
+    end_column: number;
+    start: number;
+    end: number;
+    kind: 'expression' | 'log' | 'declaration';
+    value: { type: string; [key: string]: unknown };
+    hit: number;
+    duration?: number | null;
+}
+
+export type CoverageRange = {
+    line: number;
+    column: number;
+    end_line: number;
+    end_column: number;
+    start: number;
+    end: number;
+    kind: 'statement' | 'branch';
+    hits: number;
+}
+
+export type InspectOptions = {
+    depth: number;
+    max_array_length: number;
+    max_string_length: number;
+}
+
+export type Limits = {
+    timeout_ms: number;
+    max_heap_mb: number;
+    max_results: number;
+    max_loop_iterations: number | null;
+}
+
+export type DiagnosticSpan = {
+    offset: number;
+    length: number;
+    line: number;
+    column: number;
+    end_line: number;
+    end_column: number;
+}
+
+export type DiagnosticLabel = {
+    span: DiagnosticSpan;
+    message: string | null;
+}
+
+export type Diagnostic = {
+    kind: 'parse' | 'transform' | 'runtime' | 'test';
+    severity: 'error' | 'warning' | 'advice';
+    message: string;
+    span: DiagnosticSpan | null;
+    labels: DiagnosticLabel[];
+    help: string | null;
+    stack: { function: string | null; span: DiagnosticSpan }[];
+}
+
+// Rejection of the evaluation commands, see src-tauri/src/error.rs.
+export type CommandError = {
+    kind: 'store' | 'runtime_init' | 'worker_panicked' | 'timeout';
+    message: string;
+}
+
+export type TestResult = {
+    name: string;
+    line: number;
+    column: number;
+    start: number;
+    end: number;
+    passed: boolean;
+    failure: { line: number; column: number; start: number; message: string } | null;
+}
 
 export type ITab = {
     id: string;
@@ -8,6 +98,17 @@ export type ITab = {
     result: string;
     active?: boolean;
     errors: string;
+    diagnostics?: Diagnostic[];
+    language?: Language;
+    hits?: LineHits[];
+    results?: ResultEntry[];
+    inspect?: InspectOptions;
+    session?: boolean;
+    tests?: TestResult[];
+    limits?: Limits;
+    timing?: boolean;
+    coverage?: boolean;
+    coverage_ranges?: CoverageRange[];
 }
 
 const defaultValue: ITab[] = [
@@ -17,7 +118,8 @@ const defaultValue: ITab[] = [
         content: "",
         result: "",
         active: true,
-        errors: ""
+        errors: "",
+        language: "javascript"
     }
 ];
 
@@ -37,7 +139,8 @@ export const addTab = () => {
             content: "",
             result: "",
             active: true,
-            errors: ""
+            errors: "",
+            language: "javascript"
         };
         const updatedTabs = [
             ...tabs.map((tab) => ({ ...tab, active: false })), // Deactivate all
@@ -71,6 +174,8 @@ export const removeTab = (tabId: string, tabs: ITab[]) => {
 
 
     storageStore.update(({ tabs }) => ( { tabs: tabs.filter(tab => tab.id !== tabId) } ));
+    // A closed tab's session runtime would otherwise live as long as the app.
+    invoke("reset_session", { tabId });
 
 
     if (tab.active) {